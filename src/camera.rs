@@ -0,0 +1,314 @@
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3, ortho, perspective};
+use winit::event::MouseButton;
+
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+use crate::input::{Action, InputState};
+
+// Radians per pixel of mouse movement.
+const LOOK_SENSITIVITY: f32 = 0.005;
+// Keeps the camera from flipping over when looking straight up or down.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+// Each scroll line moves the orbit camera this much closer to or away from its target.
+const ZOOM_FACTOR: f32 = 1.1;
+const MIN_ORBIT_DISTANCE: f32 = 0.1;
+// World units per second of the fly controller.
+const FLY_SPEED: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(unused)]
+pub enum Projection {
+    // Vertical field of view in degrees.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    // Height of the view volume in world units, the width follows the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unused)]
+pub enum ControllerKind {
+    Orbit,
+    Fly,
+}
+
+// Matches the `CameraUniforms` block of the scene vertex shader.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct CameraUniforms {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+}
+
+impl CameraUniforms {
+    // Draws the scene without a camera: the projection undoes the Y flip of the vertex
+    // shader, so the positions land in clip space unchanged.
+    pub fn clip_space() -> Self {
+        CameraUniforms {
+            view: Matrix4::identity().into(),
+            projection: Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0).into(),
+        }
+    }
+}
+
+// World space is right-handed with Y up. At zero yaw and pitch the camera looks down -Z.
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub projection: Projection,
+    aspect: f32,
+}
+
+impl Camera {
+    pub fn new(position: Point3<f32>, projection: Projection) -> Self {
+        Self {
+            position,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            projection,
+            aspect: 1.0,
+        }
+    }
+
+    // Called with the swap chain dimensions whenever they change.
+    pub fn set_extent(&mut self, extent: [u32; 2]) {
+        self.aspect = extent[0] as f32 / extent[1].max(1) as f32;
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+        Vector3::new(sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.forward().cross(Vector3::unit_y()).normalize()
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        let projection = match self.projection {
+            Projection::Perspective { fov_y, near, far } => perspective(Deg(fov_y), self.aspect, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                ortho(-half_width, half_width, -half_height, half_height, near, far)
+            },
+        };
+
+        vulkan_clip() * projection
+    }
+
+    pub fn uniforms(&self) -> CameraUniforms {
+        CameraUniforms {
+            view: self.view().into(),
+            projection: self.projection().into(),
+        }
+    }
+
+    pub fn view_projection(&self) -> [[f32; 4]; 4] {
+        (self.projection() * self.view()).into()
+    }
+
+    fn look(&mut self, delta: [f32; 2]) {
+        self.yaw += Rad(delta[0] * LOOK_SENSITIVITY);
+        self.pitch = Rad((self.pitch.0 - delta[1] * LOOK_SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH));
+    }
+}
+
+// cgmath builds OpenGL clip space, with Y pointing up and depth in -1..1. Vulkan has Y
+// pointing down and depth in 0..1.
+fn vulkan_clip() -> Matrix4<f32> {
+    Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, -1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    )
+}
+
+// Drag to rotate around the target, scroll to zoom.
+pub struct OrbitController {
+    target: Point3<f32>,
+    distance: f32,
+}
+
+// Drag to look around, WASD to move, E and Q to move up and down.
+pub struct FlyController {
+    // World units per second.
+    speed: f32,
+}
+
+// Turns the input of a frame into camera motion. Both controllers drag with the left
+// mouse button to rotate the camera, and the fly controller moves at the same speed
+// whatever the frame rate.
+pub enum CameraController {
+    Orbit(OrbitController),
+    Fly(FlyController),
+}
+
+impl CameraController {
+    // The orbit controller circles the world origin from the camera's current distance.
+    pub fn new(kind: ControllerKind, camera: &mut Camera) -> Self {
+        match kind {
+            ControllerKind::Orbit => {
+                let target = Point3::origin();
+                let distance = (camera.position - target).magnitude().max(MIN_ORBIT_DISTANCE);
+                let controller = OrbitController { target, distance };
+                controller.place(camera);
+                CameraController::Orbit(controller)
+            },
+            ControllerKind::Fly => CameraController::Fly(FlyController { speed: FLY_SPEED }),
+        }
+    }
+
+    // Returns true when the camera moved and the frame has to be redrawn.
+    pub fn update(&mut self, camera: &mut Camera, input: &InputState, elapsed: Duration) -> bool {
+        let drag = Some(input.cursor_delta())
+            .filter(|delta| *delta != [0.0, 0.0] && input.is_button_pressed(MouseButton::Left));
+        if let Some(delta) = drag {
+            camera.look(delta);
+        }
+
+        let moved = match self {
+            CameraController::Orbit(orbit) => orbit.update(camera, input, drag.is_some()),
+            CameraController::Fly(fly) => fly.update(camera, input, elapsed),
+        };
+
+        drag.is_some() || moved
+    }
+}
+
+impl OrbitController {
+    // Keeps the camera on its sphere around the target after a drag or a zoom.
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dragged: bool) -> bool {
+        let scroll = input.scroll_delta()[1];
+        if !dragged && scroll == 0.0 {
+            return false
+        }
+
+        self.distance = (self.distance * ZOOM_FACTOR.powf(-scroll)).max(MIN_ORBIT_DISTANCE);
+        self.place(camera);
+        true
+    }
+
+    fn place(&self, camera: &mut Camera) {
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+impl FlyController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, elapsed: Duration) -> bool {
+        let direction = camera.forward() * input.axis(Action::MoveForward, Action::MoveBack)
+            + camera.right() * input.axis(Action::MoveRight, Action::MoveLeft)
+            + Vector3::unit_y() * input.axis(Action::MoveUp, Action::MoveDown);
+        if direction.magnitude2() == 0.0 {
+            return false
+        }
+
+        camera.position += direction.normalize() * self.speed * elapsed.as_secs_f32();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::{Vector4, assert_abs_diff_eq};
+
+    use crate::render_queue::view_depth;
+
+    fn clip(camera: &Camera, position: [f32; 3]) -> Vector4<f32> {
+        let clip = Matrix4::from(camera.view_projection()) * Point3::from(position).to_homogeneous();
+        clip / clip.w
+    }
+
+    #[test]
+    fn default_orientation_looks_down_negative_z() {
+        let camera = Camera::new(Point3::origin(), Projection::Perspective { fov_y: 45.0, near: 0.1, far: 100.0 });
+
+        assert_abs_diff_eq!(camera.forward(), -Vector3::unit_z());
+        assert_abs_diff_eq!(camera.right(), Vector3::unit_x());
+    }
+
+    #[test]
+    fn yaw_turns_to_the_right() {
+        let mut camera = Camera::new(Point3::origin(), Projection::Perspective { fov_y: 45.0, near: 0.1, far: 100.0 });
+        camera.yaw = Rad(FRAC_PI_2);
+
+        assert_abs_diff_eq!(camera.forward(), Vector3::unit_x(), epsilon = 1e-6);
+        assert_abs_diff_eq!(camera.right(), Vector3::unit_z(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn look_clamps_pitch() {
+        let mut camera = Camera::new(Point3::origin(), Projection::Perspective { fov_y: 45.0, near: 0.1, far: 100.0 });
+        camera.look([0.0, -1.0e6]);
+
+        assert_eq!(camera.pitch, Rad(MAX_PITCH));
+        assert!(camera.forward().y > 0.99);
+    }
+
+    #[test]
+    fn vulkan_clip_flips_y_and_halves_depth() {
+        let clip = vulkan_clip() * Vector4::new(1.0, 1.0, -1.0, 1.0);
+        assert_abs_diff_eq!(clip, Vector4::new(1.0, -1.0, 0.0, 1.0));
+
+        let clip = vulkan_clip() * Vector4::new(0.0, 0.0, 1.0, 1.0);
+        assert_abs_diff_eq!(clip, Vector4::new(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far_to_vulkan_depth() {
+        let camera = Camera::new(Point3::origin(), Projection::Perspective { fov_y: 90.0, near: 1.0, far: 10.0 });
+
+        assert_abs_diff_eq!(clip(&camera, [0.0, 0.0, -1.0]).z, 0.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(clip(&camera, [0.0, 0.0, -10.0]).z, 1.0, epsilon = 1e-6);
+        // At 90 degrees the top of the frustum is as far up as the point is away, and
+        // Vulkan's clip space has Y pointing down.
+        assert_abs_diff_eq!(clip(&camera, [0.0, 2.0, -2.0]).y, -1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn aspect_ratio_follows_the_extent() {
+        let mut camera = Camera::new(Point3::origin(), Projection::Orthographic { height: 2.0, near: 0.1, far: 10.0 });
+        camera.set_extent([800, 400]);
+
+        assert_abs_diff_eq!(clip(&camera, [2.0, 1.0, -1.0]).x, 1.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(clip(&camera, [2.0, 1.0, -1.0]).y, -1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn zero_height_extent_keeps_a_finite_aspect() {
+        let mut camera = Camera::new(Point3::origin(), Projection::Perspective { fov_y: 45.0, near: 0.1, far: 100.0 });
+        camera.set_extent([800, 0]);
+
+        assert!(camera.aspect.is_finite());
+    }
+
+    #[test]
+    fn view_depth_is_the_distance_along_forward() {
+        let mut camera = Camera::new(Point3::new(1.0, 2.0, 3.0), Projection::Perspective { fov_y: 45.0, near: 0.1, far: 100.0 });
+        camera.yaw = Rad(0.3);
+        let view = camera.view().into();
+
+        let position = camera.position + camera.forward() * 4.0 + camera.right() * 2.0;
+        assert_abs_diff_eq!(view_depth(&view, position.into()), 4.0, epsilon = 1e-5);
+        let behind = camera.position - camera.forward();
+        assert!(view_depth(&view, behind.into()) < 0.0);
+    }
+
+    #[test]
+    fn clip_space_uniforms_undo_the_shader_flip() {
+        let uniforms = CameraUniforms::clip_space();
+        let flipped = Vector4::new(0.5, -0.5, 0.0, 1.0);
+
+        let clip = Matrix4::from(uniforms.projection) * Matrix4::from(uniforms.view) * flipped;
+        assert_abs_diff_eq!(clip, Vector4::new(0.5, 0.5, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,99 @@
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, DescriptorSetsCollection, PersistentDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::image::ImageViewAccess;
+use vulkano::pipeline::{ComputePipeline, ComputePipelineAbstract};
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::shader::EntryPointAbstract;
+use vulkano::sync::GpuFuture;
+
+use std::sync::Arc;
+
+pub struct ComputePass {
+    pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+}
+
+impl ComputePass {
+    pub fn new<Cs>(device: &Arc<Device>, entry_point: &Cs, pipeline_cache: &Arc<PipelineCache>) -> Self
+    where
+        Cs: EntryPointAbstract<SpecializationConstants = ()>,
+        Cs::PipelineLayout: Clone + Send + Sync + 'static,
+    {
+        let pipeline = ComputePipeline::new(device.clone(), entry_point, &(), Some(pipeline_cache.clone()))
+            .expect("failed to create compute pipeline!");
+
+        Self {
+            pipeline: Arc::new(pipeline),
+        }
+    }
+
+    pub fn buffer_set<B>(&self, set: usize, buffer: B) -> Arc<dyn DescriptorSet + Send + Sync>
+    where
+        B: BufferAccess + Send + Sync + 'static,
+    {
+        let layout = self.pipeline.descriptor_set_layout(set)
+            .expect("compute shader has no such descriptor set")
+            .clone();
+
+        Arc::new(PersistentDescriptorSet::start(layout)
+            .add_buffer(buffer).expect("failed to bind storage buffer")
+            .build().expect("failed to build compute descriptor set")
+        )
+    }
+
+    #[allow(unused)]
+    pub fn image_set<I>(&self, set: usize, image: I) -> Arc<dyn DescriptorSet + Send + Sync>
+    where
+        I: ImageViewAccess + Send + Sync + 'static,
+    {
+        let layout = self.pipeline.descriptor_set_layout(set)
+            .expect("compute shader has no such descriptor set")
+            .clone();
+
+        Arc::new(PersistentDescriptorSet::start(layout)
+            .add_image(image).expect("failed to bind storage image")
+            .build().expect("failed to build compute descriptor set")
+        )
+    }
+
+    // Records the dispatch into a command buffer owned by the caller, so that vulkano
+    // inserts the barriers between the compute writes and later reads in the same buffer.
+    pub fn record<S, Pc>(&self, builder: &mut AutoCommandBufferBuilder, group_counts: [u32; 3], sets: S, push_constants: Pc)
+    where
+        S: DescriptorSetsCollection,
+    {
+        builder.dispatch(group_counts, self.pipeline.clone(), sets, push_constants)
+            .expect("failed to dispatch compute shader");
+    }
+
+    // Submits the dispatch on `queue`, which may be an async compute queue, once `after`
+    // is done. Work on other queues that reads the results has to join the returned
+    // future, which signals a semaphore.
+    pub fn dispatch<F, S, Pc>(&self, after: F, queue: &Arc<Queue>, group_counts: [u32; 3], sets: S, push_constants: Pc) -> Box<dyn GpuFuture>
+    where
+        F: GpuFuture + 'static,
+        S: DescriptorSetsCollection,
+    {
+        let device = self.pipeline.device().clone();
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(device, queue.family())
+            .expect("failed to initialise compute command buffer builder");
+        self.record(&mut builder, group_counts, sets, push_constants);
+        let command_buffer = builder.build().expect("failed to build compute command buffer");
+
+        // A future without a queue, e.g. `sync::now`, has nothing to wait for.
+        let after: Box<dyn GpuFuture> = if after.queue().is_some() {
+            Box::new(after.then_signal_semaphore())
+        } else {
+            Box::new(after)
+        };
+        let future = after
+            .then_execute(queue.clone(), command_buffer)
+            .expect("failed to submit compute dispatch")
+            .then_signal_semaphore_and_flush()
+            .expect("failed to flush compute dispatch");
+
+        Box::new(future)
+    }
+}
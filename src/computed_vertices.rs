@@ -0,0 +1,162 @@
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{
+        vertex::BufferlessDefinition,
+        vertex::BufferlessVertices,
+        cache::PipelineCache,
+    };
+use vulkano::sync::GpuFuture;
+
+use std::iter;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::ConcreteGraphicsPipeline;
+use crate::compute::ComputePass;
+use crate::pipeline_desc::PipelineDesc;
+use crate::pipeline_registry::PipelineResult;
+
+const VERTEX_COUNT: u32 = 3;
+const LOCAL_SIZE_X: u32 = 64;
+
+mod compute_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shader_vertices.comp",
+        include: ["src"]
+    }
+}
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shader_computed.vert",
+        include: ["src"]
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shader_base.frag"
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ComputedVertex {
+    position: [f32; 4],
+    color: [f32; 4],
+}
+
+// Example of a compute pass producing the vertices that the graphics pipeline draws. The
+// pass runs on the compute queue, which is an async compute queue when the device has
+// one, so the vertices are shared with the graphics queue family.
+pub struct ComputedVertices {
+    queue: Arc<Queue>,
+    pass: ComputePass,
+    vertices: Arc<DeviceLocalBuffer<[ComputedVertex]>>,
+    compute_set: Arc<dyn DescriptorSet + Send + Sync>,
+    draw_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+    start: Instant,
+}
+
+impl ComputedVertices {
+    pub fn new(device: &Arc<Device>, compute_queue: &Arc<Queue>, graphics_queue: &Arc<Queue>, pipeline_cache: &Arc<PipelineCache>) -> Self {
+        let shader = compute_shader::Shader::load(device.clone())
+                .expect("failed to create compute shader module!");
+        let pass = ComputePass::new(device, &shader.main_entry_point(), pipeline_cache);
+
+        let usage = BufferUsage {
+            storage_buffer: true,
+            .. BufferUsage::none()
+        };
+
+        let vertices = DeviceLocalBuffer::array(
+                            device.clone(),
+                            VERTEX_COUNT as usize,
+                            usage,
+                            iter::once(compute_queue.family())
+                                .chain(Some(graphics_queue.family()).filter(|family| family.id() != compute_queue.family().id()))
+                        ).expect("failed to create computed vertex buffer!");
+
+        let compute_set = pass.buffer_set(0, vertices.clone());
+
+        Self {
+            queue: compute_queue.clone(),
+            pass,
+            vertices,
+            compute_set,
+            draw_set: None,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+                .expect("failed to create vertex shader module!");
+
+        let frag_shader_module = fragment_shader::Shader::load(device.clone())
+                .expect("failed to create fragment shader module!");
+
+        desc.build(
+                device,
+                BufferlessDefinition {},
+                vert_shader_module.main_entry_point(),
+                frag_shader_module.main_entry_point(),
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
+    }
+
+    pub fn set_graphics_pipeline(&mut self, graphics_pipeline: &Arc<ConcreteGraphicsPipeline>) {
+        let layout = graphics_pipeline.descriptor_set_layout(0)
+            .expect("computed vertex shader has no descriptor set")
+            .clone();
+
+        self.draw_set = Some(Arc::new(PersistentDescriptorSet::start(layout)
+            .add_buffer(self.vertices.clone()).expect("failed to bind computed vertices")
+            .build().expect("failed to build computed vertex descriptor set")
+        ));
+    }
+
+    // The compute shader places the vertices on a circle around the origin.
+    pub fn center(&self) -> [f32; 3] {
+        [0.0, 0.0, 0.0]
+    }
+
+    // Computes this frame's vertices once `after` is done. The frame drawing them joins
+    // the returned future.
+    pub fn dispatch<F>(&self, after: F) -> Box<dyn GpuFuture>
+    where
+        F: GpuFuture + 'static,
+    {
+        let push_constants = compute_shader::ty::PushConstants {
+            time: self.start.elapsed().as_secs_f32(),
+            vertex_count: VERTEX_COUNT,
+        };
+        let group_count = VERTEX_COUNT.div_ceil(LOCAL_SIZE_X);
+
+        self.pass.dispatch(after, &self.queue, [group_count, 1, 1], self.compute_set.clone(), push_constants)
+    }
+
+    pub fn record_draw(&self, builder: &mut AutoCommandBufferBuilder, graphics_pipeline: &Arc<ConcreteGraphicsPipeline>) {
+        let draw_set = self.draw_set.clone()
+            .expect("graphics pipeline not set for computed vertices");
+        let vertices = BufferlessVertices { vertices: VERTEX_COUNT as usize, instances: 1 };
+
+        builder.draw(graphics_pipeline.clone(), &DynamicState::none(), vertices, draw_set, ())
+            .expect("failed to draw");
+    }
+}
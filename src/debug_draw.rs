@@ -0,0 +1,185 @@
+use vulkano::buffer::CpuBufferPool;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{
+        cache::PipelineCache,
+        vertex::SingleBufferDefinition,
+    };
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::pipeline_desc::{BlendMode, PipelineDesc};
+use crate::pipeline_registry::{PipelineId, PipelineRegistry, PipelineResult};
+
+const SPHERE_SEGMENTS: usize = 32;
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shader_debug.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shader_debug.frag"
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+vulkano::impl_vertex!(DebugVertex, position, color);
+
+// Immediate-mode line drawing for debugging. Shapes are collected on the CPU during the
+// frame, copied into a buffer from a pool when the frame is recorded and cleared
+// afterwards, so they have to be submitted again every frame.
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+    pool: CpuBufferPool<DebugVertex>,
+    pipeline: Option<PipelineId<SingleBufferDefinition<DebugVertex>>>,
+    view_projection: [[f32; 4]; 4],
+}
+
+impl DebugDraw {
+    pub fn new(device: &Arc<Device>) -> Self {
+        Self {
+            vertices: Vec::new(),
+            pool: CpuBufferPool::vertex_buffer(device.clone()),
+            pipeline: None,
+            view_projection: IDENTITY,
+        }
+    }
+
+    pub fn register_pipeline(
+        &mut self,
+        pipelines: &mut PipelineRegistry,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        subpass: u32,
+    ) {
+        let desc = PipelineDesc {
+            blend_mode: BlendMode::Alpha,
+            .. PipelineDesc::lines()
+        };
+        self.pipeline = Some(pipelines.get_or_create("debug", &desc, render_pass, subpass, Self::create_graphics_pipeline));
+    }
+
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult<SingleBufferDefinition<DebugVertex>> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+                .expect("failed to create debug vertex shader module!");
+
+        let frag_shader_module = fragment_shader::Shader::load(device.clone())
+                .expect("failed to create debug fragment shader module!");
+
+        desc.build(
+                device,
+                SingleBufferDefinition::<DebugVertex>::new(),
+                vert_shader_module.main_entry_point(),
+                frag_shader_module.main_entry_point(),
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
+    }
+
+    // Column-major matrix applied to every position. Defaults to the identity, which
+    // makes positions normalized device coordinates.
+    pub fn set_view_projection(&mut self, view_projection: [[f32; 4]; 4]) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 4]) {
+        self.vertices.push(DebugVertex { position: from, color });
+        self.vertices.push(DebugVertex { position: to, color });
+    }
+
+    pub fn aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        let corner = |i: usize| [
+            if i & 1 == 0 { min[0] } else { max[0] },
+            if i & 2 == 0 { min[1] } else { max[1] },
+            if i & 4 == 0 { min[2] } else { max[2] },
+        ];
+
+        // Each edge joins two corners whose indices differ in a single bit.
+        for i in 0..8 {
+            for bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    pub fn sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        let point = |axis: usize, angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let mut offset = [0.0; 3];
+            offset[(axis + 1) % 3] = radius * cos;
+            offset[(axis + 2) % 3] = radius * sin;
+            [center[0] + offset[0], center[1] + offset[1], center[2] + offset[2]]
+        };
+
+        // One circle around each axis.
+        for axis in 0..3 {
+            for segment in 0..SPHERE_SEGMENTS {
+                let from = 2.0 * PI * segment as f32 / SPHERE_SEGMENTS as f32;
+                let to = 2.0 * PI * (segment + 1) as f32 / SPHERE_SEGMENTS as f32;
+                self.line(point(axis, from), point(axis, to), color);
+            }
+        }
+    }
+
+    pub fn axis(&mut self, origin: [f32; 3], size: f32) {
+        let [x, y, z] = origin;
+        self.line(origin, [x + size, y, z], [1.0, 0.0, 0.0, 1.0]);
+        self.line(origin, [x, y + size, z], [0.0, 1.0, 0.0, 1.0]);
+        self.line(origin, [x, y, z + size], [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    // Grid on the XZ plane centered at `center`, `size` wide with `divisions` cells per side.
+    pub fn grid(&mut self, center: [f32; 3], size: f32, divisions: u32, color: [f32; 4]) {
+        let half = size / 2.0;
+        let [x, y, z] = center;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions.max(1) as f32;
+            self.line([x + offset, y, z - half], [x + offset, y, z + half], color);
+            self.line([x - half, y, z + offset], [x + half, y, z + offset], color);
+        }
+    }
+
+    pub fn record_draw(&mut self, builder: &mut AutoCommandBufferBuilder, pipelines: &PipelineRegistry) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let pipeline = pipelines.get(self.pipeline.expect("debug draw pipeline not registered"));
+        let vertices = self.pool.chunk(self.vertices.drain(..))
+            .expect("failed to allocate debug vertex buffer");
+        let push_constants = vertex_shader::ty::PushConstants {
+            view_projection: self.view_projection,
+        };
+
+        builder.draw(pipeline, &DynamicState::none(), Arc::new(vertices), (), push_constants)
+            .expect("failed to draw debug lines");
+    }
+}
@@ -0,0 +1,94 @@
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::instance::PhysicalDevice;
+
+pub struct DeviceRequirements {
+    pub required_features: Features,
+    pub optional_features: Features,
+    pub required_extensions: DeviceExtensions,
+    pub optional_extensions: DeviceExtensions,
+}
+
+impl DeviceRequirements {
+    pub fn is_supported_by(&self, device: &PhysicalDevice) -> bool {
+        self.is_satisfied_by(device.supported_features(), &DeviceExtensions::supported_by_device(*device))
+    }
+
+    fn is_satisfied_by(&self, features: &Features, extensions: &DeviceExtensions) -> bool {
+        features.superset_of(&self.required_features) &&
+            extensions.intersection(&self.required_extensions) == self.required_extensions
+    }
+
+    pub fn enabled_features(&self, device: &PhysicalDevice) -> Features {
+        let optional = self.optional_features.intersection(device.supported_features());
+        features_union(&self.required_features, &optional)
+    }
+
+    pub fn enabled_extensions(&self, device: &PhysicalDevice) -> DeviceExtensions {
+        let available_extensions = DeviceExtensions::supported_by_device(*device);
+        let optional = self.optional_extensions.intersection(&available_extensions);
+        self.required_extensions.union(&optional)
+    }
+}
+
+// Features has no union() in vulkano 0.20, so build it from difference().
+fn features_union(a: &Features, b: &Features) -> Features {
+    let all = Features::all();
+    all.difference(&all.difference(a).difference(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirements() -> DeviceRequirements {
+        DeviceRequirements {
+            required_features: Features { geometry_shader: true, ..Features::none() },
+            optional_features: Features { fill_mode_non_solid: true, ..Features::none() },
+            required_extensions: DeviceExtensions { khr_swapchain: true, ..DeviceExtensions::none() },
+            optional_extensions: DeviceExtensions { ext_full_screen_exclusive: true, ..DeviceExtensions::none() },
+        }
+    }
+
+    #[test]
+    fn features_union_combines_both_sides() {
+        let a = Features { geometry_shader: true, ..Features::none() };
+        let b = Features { wide_lines: true, geometry_shader: true, ..Features::none() };
+
+        assert_eq!(features_union(&a, &b), Features { geometry_shader: true, wide_lines: true, ..Features::none() });
+        assert_eq!(features_union(&a, &Features::none()), a);
+        assert_eq!(features_union(&Features::none(), &Features::none()), Features::none());
+        assert_eq!(features_union(&a, &Features::all()), Features::all());
+    }
+
+    #[test]
+    fn satisfied_when_everything_required_is_available() {
+        let features = Features { geometry_shader: true, wide_lines: true, ..Features::none() };
+        let extensions = DeviceExtensions { khr_swapchain: true, khr_maintenance1: true, ..DeviceExtensions::none() };
+
+        assert!(requirements().is_satisfied_by(&features, &extensions));
+    }
+
+    #[test]
+    fn optional_requirements_are_not_needed() {
+        let features = Features { geometry_shader: true, ..Features::none() };
+        let extensions = DeviceExtensions { khr_swapchain: true, ..DeviceExtensions::none() };
+
+        assert!(requirements().is_satisfied_by(&features, &extensions));
+    }
+
+    #[test]
+    fn missing_required_feature_is_not_satisfied() {
+        let features = Features { fill_mode_non_solid: true, ..Features::none() };
+        let extensions = DeviceExtensions { khr_swapchain: true, ..DeviceExtensions::none() };
+
+        assert!(!requirements().is_satisfied_by(&features, &extensions));
+    }
+
+    #[test]
+    fn missing_required_extension_is_not_satisfied() {
+        let features = Features::all();
+        let extensions = DeviceExtensions { ext_full_screen_exclusive: true, ..DeviceExtensions::none() };
+
+        assert!(!requirements().is_satisfied_by(&features, &extensions));
+    }
+}
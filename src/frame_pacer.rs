@@ -0,0 +1,152 @@
+use winit::event_loop::ControlFlow;
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Continuous,
+    OnDemand,
+}
+
+pub struct FramePacer {
+    frame_duration: Option<Duration>,
+    mode: RenderMode,
+    next_frame: Instant,
+    dirty: bool,
+}
+
+impl FramePacer {
+    pub fn new(target_fps: Option<u32>, mode: RenderMode) -> Self {
+        Self {
+            frame_duration: Self::frame_duration(target_fps),
+            mode,
+            next_frame: Instant::now(),
+            dirty: true,
+        }
+    }
+
+    fn frame_duration(target_fps: Option<u32>) -> Option<Duration> {
+        target_fps
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / f64::from(fps)))
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.frame_duration = Self::frame_duration(target_fps);
+        self.next_frame = Instant::now();
+    }
+
+    pub fn request_redraw(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn should_render(&self, now: Instant) -> bool {
+        let wanted = match self.mode {
+            RenderMode::Continuous => true,
+            RenderMode::OnDemand => self.dirty,
+        };
+
+        wanted && now >= self.next_frame
+    }
+
+    pub fn frame_rendered(&mut self, now: Instant) {
+        self.dirty = false;
+
+        if let Some(frame_duration) = self.frame_duration {
+            self.next_frame += frame_duration;
+            if self.next_frame < now {
+                self.next_frame = now + frame_duration;
+            }
+        }
+    }
+
+    pub fn control_flow(&self) -> ControlFlow {
+        if self.mode == RenderMode::OnDemand && !self.dirty {
+            return ControlFlow::Wait
+        }
+
+        match self.frame_duration {
+            Some(_) => ControlFlow::WaitUntil(self.next_frame),
+            None => ControlFlow::Poll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continuous_without_target_renders_every_time() {
+        let mut pacer = FramePacer::new(None, RenderMode::Continuous);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(pacer.should_render(now));
+            pacer.frame_rendered(now);
+        }
+        assert_eq!(pacer.control_flow(), ControlFlow::Poll);
+    }
+
+    #[test]
+    fn zero_target_fps_is_unlimited() {
+        let pacer = FramePacer::new(Some(0), RenderMode::Continuous);
+        assert_eq!(pacer.control_flow(), ControlFlow::Poll);
+    }
+
+    #[test]
+    fn target_fps_waits_for_next_frame() {
+        let mut pacer = FramePacer::new(Some(50), RenderMode::Continuous);
+        let start = Instant::now();
+        let frame = Duration::from_millis(20);
+
+        assert!(pacer.should_render(start));
+        pacer.frame_rendered(start);
+
+        assert!(!pacer.should_render(start + frame / 2));
+        assert!(pacer.should_render(pacer.next_frame));
+        assert_eq!(pacer.control_flow(), ControlFlow::WaitUntil(pacer.next_frame));
+        assert!(pacer.next_frame > start && pacer.next_frame <= start + frame + frame);
+    }
+
+    #[test]
+    fn late_frame_does_not_burst_to_catch_up() {
+        let mut pacer = FramePacer::new(Some(50), RenderMode::Continuous);
+        let start = Instant::now();
+        pacer.frame_rendered(start);
+
+        let late = start + Duration::from_secs(1);
+        pacer.frame_rendered(late);
+
+        assert!(!pacer.should_render(late));
+        assert_eq!(pacer.next_frame, late + Duration::from_millis(20));
+    }
+
+    #[test]
+    fn on_demand_renders_only_when_requested() {
+        let mut pacer = FramePacer::new(None, RenderMode::OnDemand);
+        let now = Instant::now();
+
+        assert!(pacer.should_render(now));
+        pacer.frame_rendered(now);
+
+        assert!(!pacer.should_render(now));
+        assert_eq!(pacer.control_flow(), ControlFlow::Wait);
+
+        pacer.request_redraw();
+        assert!(pacer.should_render(now));
+        assert_eq!(pacer.control_flow(), ControlFlow::Poll);
+    }
+
+    #[test]
+    fn on_demand_request_still_respects_target_fps() {
+        let mut pacer = FramePacer::new(Some(50), RenderMode::OnDemand);
+        let start = Instant::now();
+        pacer.frame_rendered(start);
+
+        pacer.request_redraw();
+        assert!(!pacer.should_render(start));
+        assert_eq!(pacer.control_flow(), ControlFlow::WaitUntil(pacer.next_frame));
+        assert!(pacer.should_render(pacer.next_frame));
+    }
+}
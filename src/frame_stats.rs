@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTiming {
+    pub cpu: Duration,
+    pub acquire: Duration,
+    pub submit: Duration,
+    // Measured with timestamp queries, so it belongs to a frame that finished earlier.
+    // `None` without timestamps or while no frame has finished yet.
+    pub gpu: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameSummary {
+    pub fps: f64,
+    // The GPU average only counts the frames that had a GPU time.
+    pub average: FrameTiming,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl fmt::Display for FrameSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} fps | cpu {:.2} ms (p50 {:.2}, p95 {:.2}, p99 {:.2}) | acquire {:.2} ms | submit {:.2} ms",
+            self.fps,
+            millis(self.average.cpu),
+            millis(self.p50),
+            millis(self.p95),
+            millis(self.p99),
+            millis(self.average.acquire),
+            millis(self.average.submit),
+        )?;
+
+        if let Some(gpu) = self.average.gpu {
+            write!(f, " | gpu {:.2} ms", millis(gpu))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct FrameStats {
+    samples: VecDeque<FrameTiming>,
+    capacity: usize,
+    frame_index: u64,
+    frames_since_report: u32,
+    last_report: Instant,
+    csv: Option<BufWriter<File>>,
+}
+
+impl FrameStats {
+    // A capacity of zero keeps no history: fps and the CSV export still work,
+    // but the summary times stay at zero.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            frame_index: 0,
+            frames_since_report: 0,
+            last_report: Instant::now(),
+            csv: None,
+        }
+    }
+
+    pub fn export_csv<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frame,cpu_ms,acquire_ms,submit_ms,gpu_ms")?;
+        self.csv = Some(writer);
+        Ok(())
+    }
+
+    pub fn record(&mut self, timing: FrameTiming) {
+        if self.capacity > 0 {
+            if self.samples.len() == self.capacity {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(timing);
+        }
+
+        if let Some(writer) = self.csv.as_mut() {
+            let gpu = timing.gpu.map(|gpu| format!("{:.4}", millis(gpu))).unwrap_or_default();
+            let result = writeln!(writer, "{},{:.4},{:.4},{:.4},{}",
+                self.frame_index,
+                millis(timing.cpu),
+                millis(timing.acquire),
+                millis(timing.submit),
+                gpu,
+            );
+
+            if let Err(err) = result {
+                println!("failed to write frame stats: {:?}", err);
+                self.csv = None;
+            }
+        }
+
+        self.frame_index += 1;
+        self.frames_since_report += 1;
+    }
+
+    pub fn report(&mut self, now: Instant) -> Option<FrameSummary> {
+        let elapsed = now.duration_since(self.last_report);
+        if elapsed < REPORT_INTERVAL || self.frames_since_report == 0 {
+            return None
+        }
+
+        let fps = f64::from(self.frames_since_report) / elapsed.as_secs_f64();
+        self.frames_since_report = 0;
+        self.last_report = now;
+
+        if let Some(writer) = self.csv.as_mut() {
+            let _ = writer.flush();
+        }
+
+        Some(self.summary(fps))
+    }
+
+    fn summary(&self, fps: f64) -> FrameSummary {
+        let count = (self.samples.len() as u32).max(1);
+        let total = self.samples.iter().fold(FrameTiming::default(), |acc, timing| FrameTiming {
+            cpu: acc.cpu + timing.cpu,
+            acquire: acc.acquire + timing.acquire,
+            submit: acc.submit + timing.submit,
+            gpu: add_gpu(acc.gpu, timing.gpu),
+        });
+        let gpu_count = self.samples.iter().filter(|timing| timing.gpu.is_some()).count() as u32;
+
+        let mut cpu_times: Vec<Duration> = self.samples.iter().map(|timing| timing.cpu).collect();
+        cpu_times.sort();
+
+        FrameSummary {
+            fps,
+            average: FrameTiming {
+                cpu: total.cpu / count,
+                acquire: total.acquire / count,
+                submit: total.submit / count,
+                gpu: total.gpu.map(|gpu| gpu / gpu_count),
+            },
+            p50: percentile(&cpu_times, 0.50),
+            p95: percentile(&cpu_times, 0.95),
+            p99: percentile(&cpu_times, 0.99),
+        }
+    }
+}
+
+// Sums GPU times that may be missing, staying `None` only when both are.
+pub fn add_gpu(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+// Nearest-rank percentile of an ascending slice, zero when there are no samples.
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default()
+    }
+
+    let rank = (fraction * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn stats_with(cpu_times: &[u64]) -> FrameStats {
+        let mut stats = FrameStats::new(cpu_times.len().max(1));
+        for cpu in cpu_times {
+            stats.record(FrameTiming { cpu: ms(*cpu), acquire: ms(1), submit: ms(2), gpu: None });
+        }
+        stats
+    }
+
+    #[test]
+    fn percentile_of_no_samples_is_zero() {
+        assert_eq!(percentile(&[], 0.5), Duration::default());
+    }
+
+    #[test]
+    fn percentile_of_single_sample_is_that_sample() {
+        let sorted = [ms(7)];
+        assert_eq!(percentile(&sorted, 0.0), ms(7));
+        assert_eq!(percentile(&sorted, 0.5), ms(7));
+        assert_eq!(percentile(&sorted, 1.0), ms(7));
+    }
+
+    #[test]
+    fn percentile_picks_nearest_rank() {
+        let sorted: Vec<Duration> = (1..=11).map(ms).collect();
+        assert_eq!(percentile(&sorted, 0.0), ms(1));
+        assert_eq!(percentile(&sorted, 0.5), ms(6));
+        assert_eq!(percentile(&sorted, 0.95), ms(11));
+        assert_eq!(percentile(&sorted, 1.0), ms(11));
+
+        let sorted: Vec<Duration> = (1..=100).map(ms).collect();
+        assert_eq!(percentile(&sorted, 0.95), ms(95));
+        assert_eq!(percentile(&sorted, 0.99), ms(99));
+    }
+
+    #[test]
+    fn summary_of_no_samples_is_zero() {
+        let summary = stats_with(&[]).summary(0.0);
+        assert_eq!(summary.average.cpu, Duration::default());
+        assert_eq!(summary.p99, Duration::default());
+    }
+
+    #[test]
+    fn summary_of_single_sample() {
+        let summary = stats_with(&[4]).summary(60.0);
+        assert_eq!(summary.fps, 60.0);
+        assert_eq!(summary.average.cpu, ms(4));
+        assert_eq!(summary.average.acquire, ms(1));
+        assert_eq!(summary.average.submit, ms(2));
+        assert_eq!(summary.p50, ms(4));
+        assert_eq!(summary.p99, ms(4));
+    }
+
+    #[test]
+    fn summary_sorts_samples_before_taking_percentiles() {
+        let summary = stats_with(&[9, 1, 5, 3, 7]).summary(0.0);
+        assert_eq!(summary.average.cpu, ms(5));
+        assert_eq!(summary.p50, ms(5));
+        assert_eq!(summary.p95, ms(9));
+    }
+
+    #[test]
+    fn samples_are_limited_to_capacity() {
+        let mut stats = FrameStats::new(2);
+        for cpu in &[100, 1, 3] {
+            stats.record(FrameTiming { cpu: ms(*cpu), ..FrameTiming::default() });
+        }
+
+        let summary = stats.summary(0.0);
+        assert_eq!(summary.average.cpu, ms(2));
+        assert_eq!(summary.p99, ms(3));
+    }
+
+    #[test]
+    fn zero_capacity_keeps_no_history() {
+        let mut stats = FrameStats::new(0);
+        for cpu in &[4, 8] {
+            stats.record(FrameTiming { cpu: ms(*cpu), ..FrameTiming::default() });
+        }
+        assert!(stats.samples.is_empty());
+
+        let now = stats.last_report + REPORT_INTERVAL;
+        let summary = stats.report(now).expect("a summary after the report interval");
+        assert_eq!(summary.fps, 2.0);
+        assert_eq!(summary.average.cpu, Duration::default());
+    }
+
+    #[test]
+    fn gpu_average_skips_frames_without_gpu_time() {
+        let mut stats = FrameStats::new(4);
+        for gpu in &[None, Some(ms(3)), None, Some(ms(5))] {
+            stats.record(FrameTiming { gpu: *gpu, ..FrameTiming::default() });
+        }
+        assert_eq!(stats.summary(0.0).average.gpu, Some(ms(4)));
+
+        let summary = stats_with(&[1, 2]).summary(0.0);
+        assert_eq!(summary.average.gpu, None);
+    }
+}
@@ -0,0 +1,488 @@
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{
+        AutoCommandBuffer,
+        CommandBuffer,
+        CommandBufferExecError,
+        Kind,
+        KindOcclusionQuery,
+        SubpassContents,
+    };
+use vulkano::command_buffer::pool::standard::{StandardCommandPoolAlloc, StandardCommandPoolBuilder};
+use vulkano::command_buffer::sys::{
+        Flags,
+        UnsafeCommandBuffer,
+        UnsafeCommandBufferBuilder,
+        UnsafeCommandBufferBuilderExecuteCommands,
+        UnsafeCommandBufferBuilderPipelineBarrier,
+    };
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::format::ClearValue;
+use vulkano::framebuffer::{FramebufferAbstract, RenderPassAbstract};
+use vulkano::image::{ImageAccess, ImageLayout};
+use vulkano::query::{QueryPipelineStatisticFlags, QueryType, UnsafeQueryPool};
+use vulkano::sync::{AccessCheckError, AccessFlagBits, FenceSignalFuture, GpuFuture, PipelineStages};
+
+use std::any::Any;
+use std::fmt;
+use std::iter;
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+
+// The fence of a submitted frame. The window keeps a clone as the end of its previous
+// frame, the queries keep one to know when their results can be read.
+pub type FrameFence = Arc<FenceSignalFuture<Box<dyn GpuFuture>>>;
+
+type RawBuilder = UnsafeCommandBufferBuilder<StandardCommandPoolBuilder>;
+
+// A primary command buffer recorded with vulkano's unsafe builder, for the query
+// commands `AutoCommandBufferBuilder` doesn't have in vulkano 0.20. Nothing is tracked:
+// the resources the commands use are only kept alive, and the caller orders the
+// submissions.
+pub struct RawCommandBuffer {
+    inner: UnsafeCommandBuffer<StandardCommandPoolAlloc>,
+    #[allow(unused)]
+    resources: Vec<Arc<dyn Any + Send + Sync>>,
+}
+
+impl RawCommandBuffer {
+    pub fn record<F>(queue: &Arc<Queue>, resources: Vec<Arc<dyn Any + Send + Sync>>, commands: F) -> Self
+    where
+        F: FnOnce(&mut RawBuilder),
+    {
+        let pool = Device::standard_command_pool(queue.device(), queue.family());
+        let inner = unsafe {
+            let mut builder = UnsafeCommandBufferBuilder::new(&pool, Kind::primary(), Flags::OneTimeSubmit)
+                .expect("failed to initialise query command buffer builder");
+            commands(&mut builder);
+            builder.build().expect("failed to build query command buffer")
+        };
+
+        Self { inner, resources }
+    }
+}
+
+unsafe impl DeviceOwned for RawCommandBuffer {
+    fn device(&self) -> &Arc<Device> {
+        self.inner.device()
+    }
+}
+
+unsafe impl CommandBuffer for RawCommandBuffer {
+    type PoolAlloc = StandardCommandPoolAlloc;
+
+    fn inner(&self) -> &UnsafeCommandBuffer<StandardCommandPoolAlloc> {
+        &self.inner
+    }
+
+    fn lock_submit(&self, _future: &dyn GpuFuture, _queue: &Queue) -> Result<(), CommandBufferExecError> {
+        Ok(())
+    }
+
+    unsafe fn unlock(&self) {}
+
+    fn check_buffer_access(&self, _buffer: &dyn BufferAccess, _exclusive: bool, _queue: &Queue)
+        -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError>
+    {
+        Err(AccessCheckError::Unknown)
+    }
+
+    fn check_image_access(&self, _image: &dyn ImageAccess, _layout: ImageLayout, _exclusive: bool, _queue: &Queue)
+        -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError>
+    {
+        Err(AccessCheckError::Unknown)
+    }
+
+    fn kind(&self) -> Kind<&dyn RenderPassAbstract, &dyn FramebufferAbstract> {
+        Kind::Primary
+    }
+}
+
+// The query results of one frame in flight, one buffer per query pool. They are copied
+// into host visible buffers at the end of the frame and read once its fence is signaled.
+struct QueryFrame {
+    results: Vec<Arc<CpuAccessibleBuffer<[u32]>>>,
+    fence: Option<FrameFence>,
+}
+
+impl QueryFrame {
+    fn new(device: &Arc<Device>, lens: &[usize]) -> Self {
+        let usage = BufferUsage { transfer_destination: true, .. BufferUsage::none() };
+        let results = lens.iter()
+            .map(|len| {
+                CpuAccessibleBuffer::from_iter(device.clone(), usage, true, vec![0; *len].into_iter())
+                    .expect("failed to create query results buffer")
+            }).collect();
+
+        Self { results, fence: None }
+    }
+
+    // Waits for the frame that last used these queries and returns its results.
+    fn take_results(&mut self) -> Option<Vec<Vec<u32>>> {
+        let fence = self.fence.take()?;
+        fence.wait(None).ok()?;
+        self.results.iter()
+            .map(|results| results.read().ok().map(|results| results.to_vec()))
+            .collect()
+    }
+}
+
+// Copies `count` queries of `values` results each. The copy waits for the queries
+// written before it, and the results are visible to the host once the frame's fence is
+// signaled.
+unsafe fn copy_results(
+    builder: &mut RawBuilder,
+    pool: &UnsafeQueryPool,
+    first: u32,
+    count: u32,
+    values: usize,
+    results: &CpuAccessibleBuffer<[u32]>,
+) {
+    let mut barrier = UnsafeCommandBufferBuilderPipelineBarrier::new();
+    barrier.add_execution_dependency(
+        PipelineStages { all_commands: true, .. PipelineStages::none() },
+        PipelineStages { transfer: true, .. PipelineStages::none() },
+        false,
+    );
+    builder.pipeline_barrier(&barrier);
+
+    builder.copy_query_pool_results(pool.queries_range(first, count).unwrap(), results, values * mem::size_of::<u32>());
+
+    let mut barrier = UnsafeCommandBufferBuilderPipelineBarrier::new();
+    barrier.add_memory_barrier(
+        PipelineStages { transfer: true, .. PipelineStages::none() },
+        AccessFlagBits { transfer_write: true, .. AccessFlagBits::none() },
+        PipelineStages { host: true, .. PipelineStages::none() },
+        AccessFlagBits { host_read: true, .. AccessFlagBits::none() },
+        false,
+    );
+    builder.pipeline_barrier(&barrier);
+}
+
+// Measures the GPU time of a window's frames with two timestamps, written before and
+// after the frame's command buffer. Every frame in flight has its own pair of queries,
+// read back once the frame that wrote them has finished.
+pub struct GpuTimer {
+    queue: Arc<Queue>,
+    pool: Arc<UnsafeQueryPool>,
+    frames: Vec<QueryFrame>,
+    current: usize,
+    valid_bits: u32,
+    period: f32,
+}
+
+impl GpuTimer {
+    // `None` when the queue doesn't support timestamps.
+    pub fn new(device: &Arc<Device>, queue: &Arc<Queue>, frames_in_flight: usize) -> Option<Self> {
+        let valid_bits = queue.family().timestamp_valid_bits()?;
+
+        // `queries_range` rejects ranges that end at the last slot, so there is one more.
+        let slots = 2 * frames_in_flight as u32 + 1;
+        let pool = UnsafeQueryPool::new(device.clone(), QueryType::Timestamp, slots)
+            .expect("failed to create timestamp query pool");
+
+        Some(Self {
+            queue: queue.clone(),
+            pool: Arc::new(pool),
+            frames: (0..frames_in_flight).map(|_| QueryFrame::new(device, &[2])).collect(),
+            current: 0,
+            valid_bits,
+            period: device.physical_device().limits().timestamp_period(),
+        })
+    }
+
+    fn first_query(&self) -> u32 {
+        2 * self.current as u32
+    }
+
+    // GPU time of the frame that last used the queries of the next frame, waiting for it
+    // if it is still running. `None` until every set of queries was used once.
+    pub fn resolve(&mut self) -> Option<Duration> {
+        let results = self.frames[self.current].take_results()?;
+        let timestamps = &results[0];
+        Some(elapsed(timestamps[0], timestamps[1], self.valid_bits, self.period))
+    }
+
+    // Submitted right before the frame's command buffer.
+    pub fn begin(&self) -> RawCommandBuffer {
+        let pool = self.pool.clone();
+        let first = self.first_query();
+        RawCommandBuffer::record(&self.queue, vec![pool.clone()], |builder| unsafe {
+            builder.reset_query_pool(pool.queries_range(first, 2).unwrap());
+            builder.write_timestamp(pool.query(first).unwrap(), PipelineStages { top_of_pipe: true, .. PipelineStages::none() });
+        })
+    }
+
+    // Submitted right after the frame's command buffer. Timestamps are ordered by
+    // submission on the queue, so this one waits for every command of the frame.
+    pub fn end(&self) -> RawCommandBuffer {
+        let pool = self.pool.clone();
+        let results = self.frames[self.current].results[0].clone();
+        let first = self.first_query();
+        RawCommandBuffer::record(&self.queue, vec![pool.clone(), results.clone()], |builder| unsafe {
+            builder.write_timestamp(pool.query(first + 1).unwrap(), PipelineStages { bottom_of_pipe: true, .. PipelineStages::none() });
+            copy_results(builder, &pool, first, 2, 1, &results);
+        })
+    }
+
+    // Moves on to the queries of the next frame. Without a fence the frame wasn't
+    // submitted and its results are dropped.
+    pub fn submitted(&mut self, fence: Option<FrameFence>) {
+        self.frames[self.current].fence = fence;
+        self.current = (self.current + 1) % self.frames.len();
+    }
+}
+
+// Results of the pipeline statistics query, in the order of the flag bits.
+const STATISTICS_VALUES: usize = 4;
+
+fn statistics_flags() -> QueryPipelineStatisticFlags {
+    QueryPipelineStatisticFlags {
+        input_assembly_vertices: true,
+        input_assembly_primitives: true,
+        vertex_shader_invocations: true,
+        fragment_shader_invocations: true,
+        .. QueryPipelineStatisticFlags::none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineCounts {
+    pub vertices: u32,
+    pub primitives: u32,
+    pub vertex_invocations: u32,
+    pub fragment_invocations: u32,
+}
+
+impl PipelineCounts {
+    fn from_results(values: &[u32]) -> Self {
+        Self {
+            vertices: values[0],
+            primitives: values[1],
+            vertex_invocations: values[2],
+            fragment_invocations: values[3],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SceneCounts {
+    // Samples that passed the fragment tests. Without precise occlusion queries any
+    // non-zero value only means that some did.
+    pub samples: u32,
+    // `None` when the device has no pipeline statistics queries.
+    pub pipeline: Option<PipelineCounts>,
+}
+
+impl fmt::Display for SceneCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} samples", self.samples)?;
+
+        if let Some(pipeline) = self.pipeline {
+            write!(f, " | {} vertices, {} primitives, {} vs / {} fs invocations",
+                pipeline.vertices,
+                pipeline.primitives,
+                pipeline.vertex_invocations,
+                pipeline.fragment_invocations,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn execute(command_buffer: &AutoCommandBuffer) -> UnsafeCommandBufferBuilderExecuteCommands {
+    let mut commands = UnsafeCommandBufferBuilderExecuteCommands::new();
+    commands.add(command_buffer);
+    commands
+}
+
+// Counts the samples and the pipeline work of the scene subpass with an occlusion and
+// a pipeline statistics query. Queries have to begin and end in the command buffer that
+// draws, so the frame is recorded as secondary command buffers executed by a primary
+// one that holds the render pass and the queries.
+pub struct SceneQueries {
+    queue: Arc<Queue>,
+    occlusion: Arc<UnsafeQueryPool>,
+    statistics: Option<Arc<UnsafeQueryPool>>,
+    precise: bool,
+    frames: Vec<QueryFrame>,
+    current: usize,
+}
+
+impl SceneQueries {
+    // `None` when the device can't execute secondary command buffers while a query is
+    // active.
+    pub fn new(device: &Arc<Device>, queue: &Arc<Queue>, frames_in_flight: usize) -> Option<Self> {
+        let features = device.enabled_features();
+        if !features.inherited_queries {
+            return None
+        }
+
+        // `queries_range` rejects ranges that end at the last slot, so there is one more.
+        let slots = frames_in_flight as u32 + 1;
+        let occlusion = UnsafeQueryPool::new(device.clone(), QueryType::Occlusion, slots)
+            .expect("failed to create occlusion query pool");
+        let statistics = if features.pipeline_statistics_query {
+            let pool = UnsafeQueryPool::new(device.clone(), QueryType::PipelineStatistics(statistics_flags()), slots)
+                .expect("failed to create pipeline statistics query pool");
+            Some(Arc::new(pool))
+        } else {
+            None
+        };
+
+        let lens: &[usize] = if statistics.is_some() { &[1, STATISTICS_VALUES] } else { &[1] };
+
+        Some(Self {
+            queue: queue.clone(),
+            occlusion: Arc::new(occlusion),
+            statistics,
+            precise: features.occlusion_query_precise,
+            frames: (0..frames_in_flight).map(|_| QueryFrame::new(device, lens)).collect(),
+            current: 0,
+        })
+    }
+
+    // What the secondary command buffer of the scene subpass inherits.
+    pub fn occlusion_query(&self) -> KindOcclusionQuery {
+        KindOcclusionQuery::Allowed { control_precise_allowed: self.precise }
+    }
+
+    pub fn statistics_flags(&self) -> QueryPipelineStatisticFlags {
+        match self.statistics {
+            Some(_) => statistics_flags(),
+            None => QueryPipelineStatisticFlags::none(),
+        }
+    }
+
+    // Counts of the frame that last used the queries of the next frame, waiting for it if
+    // it is still running.
+    pub fn resolve(&mut self) -> Option<SceneCounts> {
+        let results = self.frames[self.current].take_results()?;
+        Some(SceneCounts {
+            samples: results[0][0],
+            pipeline: results.get(1).map(|values| PipelineCounts::from_results(values)),
+        })
+    }
+
+    // The primary command buffer of a frame. `updates` is a secondary compute command
+    // buffer run before the render pass, `scene` and `overlay` draw the two subpasses.
+    pub fn record_frame(
+        &self,
+        framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+        clear_color: [f32; 4],
+        updates: Arc<AutoCommandBuffer>,
+        scene: Arc<AutoCommandBuffer>,
+        overlay: Arc<AutoCommandBuffer>,
+    ) -> RawCommandBuffer {
+        let query = self.current as u32;
+        let occlusion = self.occlusion.clone();
+        let statistics = self.statistics.clone();
+        let precise = self.precise;
+        let results = self.frames[self.current].results.clone();
+
+        let mut resources: Vec<Arc<dyn Any + Send + Sync>> = vec![
+            occlusion.clone(),
+            Arc::new(framebuffer.clone()),
+            updates.clone(),
+            scene.clone(),
+            overlay.clone(),
+        ];
+        resources.extend(statistics.iter().map(|pool| pool.clone() as Arc<dyn Any + Send + Sync>));
+        resources.extend(results.iter().map(|results| results.clone() as Arc<dyn Any + Send + Sync>));
+
+        RawCommandBuffer::record(&self.queue, resources, |builder| unsafe {
+            builder.reset_query_pool(occlusion.queries_range(query, 1).unwrap());
+            if let Some(statistics) = &statistics {
+                builder.reset_query_pool(statistics.queries_range(query, 1).unwrap());
+            }
+
+            // The updates write the buffers the scene reads as vertices or in its vertex
+            // shader.
+            builder.execute_commands(execute(&updates));
+            let mut barrier = UnsafeCommandBufferBuilderPipelineBarrier::new();
+            barrier.add_memory_barrier(
+                PipelineStages { compute_shader: true, .. PipelineStages::none() },
+                AccessFlagBits { shader_write: true, .. AccessFlagBits::none() },
+                PipelineStages { vertex_input: true, vertex_shader: true, .. PipelineStages::none() },
+                AccessFlagBits { vertex_attribute_read: true, shader_read: true, .. AccessFlagBits::none() },
+                false,
+            );
+            builder.pipeline_barrier(&barrier);
+
+            builder.begin_render_pass(&*framebuffer, SubpassContents::SecondaryCommandBuffers, iter::once(ClearValue::Float(clear_color)));
+            builder.begin_query(occlusion.query(query).unwrap(), precise);
+            if let Some(statistics) = &statistics {
+                builder.begin_query(statistics.query(query).unwrap(), false);
+            }
+            builder.execute_commands(execute(&scene));
+            if let Some(statistics) = &statistics {
+                builder.end_query(statistics.query(query).unwrap());
+            }
+            builder.end_query(occlusion.query(query).unwrap());
+
+            builder.next_subpass(SubpassContents::SecondaryCommandBuffers);
+            builder.execute_commands(execute(&overlay));
+            builder.end_render_pass();
+
+            copy_results(builder, &occlusion, query, 1, 1, &results[0]);
+            if let Some(statistics) = &statistics {
+                copy_results(builder, statistics, query, 1, STATISTICS_VALUES, &results[1]);
+            }
+        })
+    }
+
+    // Moves on to the queries of the next frame. Without a fence the frame wasn't
+    // submitted and its results are dropped.
+    pub fn submitted(&mut self, fence: Option<FrameFence>) {
+        self.frames[self.current].fence = fence;
+        self.current = (self.current + 1) % self.frames.len();
+    }
+}
+
+// Time between two timestamps of `valid_bits` bits, `period` nanoseconds apart per tick.
+// The results are copied as 32 bit values, so a counter that wrapped in between is
+// handled the same way as one that overflowed its valid bits.
+fn elapsed(start: u32, end: u32, valid_bits: u32, period: f32) -> Duration {
+    let mask = if valid_bits >= 32 { u32::MAX } else { (1 << valid_bits) - 1 };
+    let ticks = end.wrapping_sub(start) & mask;
+    Duration::from_nanos((f64::from(ticks) * f64::from(period)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_scales_ticks_by_period() {
+        assert_eq!(elapsed(100, 1100, 64, 1.0), Duration::from_micros(1));
+        assert_eq!(elapsed(100, 1100, 64, 52.5), Duration::from_nanos(52500));
+    }
+
+    #[test]
+    fn elapsed_handles_wrapped_counter() {
+        assert_eq!(elapsed(u32::MAX - 9, 10, 64, 1.0), Duration::from_nanos(20));
+    }
+
+    #[test]
+    fn elapsed_masks_invalid_bits() {
+        assert_eq!(elapsed(0xff0, 0x010, 12, 1.0), Duration::from_nanos(0x20));
+        assert_eq!(elapsed(0, 0, 36, 1.0), Duration::default());
+    }
+
+    #[test]
+    fn pipeline_counts_follow_flag_order() {
+        let counts = PipelineCounts::from_results(&[3, 1, 3, 1200]);
+        assert_eq!(counts, PipelineCounts { vertices: 3, primitives: 1, vertex_invocations: 3, fragment_invocations: 1200 });
+    }
+
+    #[test]
+    fn scene_counts_show_pipeline_counts_when_present() {
+        let counts = SceneCounts { samples: 1200, pipeline: None };
+        assert_eq!(counts.to_string(), "1200 samples");
+
+        let pipeline = PipelineCounts { vertices: 3, primitives: 1, vertex_invocations: 3, fragment_invocations: 1200 };
+        let counts = SceneCounts { samples: 1200, pipeline: Some(pipeline) };
+        assert_eq!(counts.to_string(), "1200 samples | 3 vertices, 1 primitives, 3 vs / 1200 fs invocations");
+    }
+}
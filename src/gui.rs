@@ -0,0 +1,440 @@
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet, UnsafeDescriptorSetLayout};
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::image::ImmutableImage;
+use vulkano::pipeline::{
+        cache::PipelineCache,
+        vertex::SingleBufferDefinition,
+        viewport::Scissor,
+    };
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use winit::window::Window;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::pipeline_desc::{BlendMode, CullMode, PipelineDesc};
+use crate::pipeline_registry::{PipelineId, PipelineRegistry, PipelineResult};
+use crate::uploader::Uploader;
+
+// Lines scrolled per mouse wheel notch, in points.
+const SCROLL_LINE_HEIGHT: f32 = 24.0;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shader_gui.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shader_gui.frag"
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct GuiVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+vulkano::impl_vertex!(GuiVertex, position, uv, color);
+
+// egui overlay drawn in its own subpass after the scene. Window events are turned into
+// egui input as they arrive, `run` lays out the UI and tessellates it, and `record_draw`
+// streams the meshes through buffer pools, one draw per clip rectangle.
+pub struct GuiOverlay {
+    context: egui::CtxRef,
+    input: egui::RawInput,
+    start: Instant,
+    scale_factor: f32,
+    pointer_position: egui::Pos2,
+    modifiers: egui::Modifiers,
+
+    meshes: Vec<egui::ClippedMesh>,
+    screen_size: [f32; 2],
+    needs_repaint: bool,
+
+    vertex_pool: CpuBufferPool<GuiVertex>,
+    index_pool: CpuBufferPool<u32>,
+    sampler: Arc<Sampler>,
+    font_version: Option<u64>,
+    font_image: Option<Arc<ImmutableImage<Format>>>,
+
+    pipeline: Option<PipelineId<SingleBufferDefinition<GuiVertex>>>,
+    font_layout: Option<Arc<UnsafeDescriptorSetLayout>>,
+    font_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+}
+
+impl GuiOverlay {
+    pub fn new(device: &Arc<Device>, window: &Window) -> Self {
+        let sampler = Sampler::new(
+                            device.clone(),
+                            Filter::Linear,
+                            Filter::Linear,
+                            MipmapMode::Nearest,
+                            SamplerAddressMode::ClampToEdge,
+                            SamplerAddressMode::ClampToEdge,
+                            SamplerAddressMode::ClampToEdge,
+                            0.0, 1.0, 0.0, 0.0
+                        ).expect("failed to create gui sampler!");
+
+        Self {
+            context: egui::CtxRef::default(),
+            input: egui::RawInput::default(),
+            start: Instant::now(),
+            scale_factor: window.scale_factor() as f32,
+            pointer_position: egui::Pos2::ZERO,
+            modifiers: egui::Modifiers::default(),
+
+            meshes: Vec::new(),
+            screen_size: [1.0, 1.0],
+            needs_repaint: false,
+
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            index_pool: CpuBufferPool::new(device.clone(), BufferUsage::index_buffer()),
+            sampler,
+            font_version: None,
+            font_image: None,
+
+            pipeline: None,
+            font_layout: None,
+            font_set: None,
+        }
+    }
+
+    // The font atlas may only arrive with the first `run`, so the descriptor set layout
+    // is kept to build the font set whenever egui changes the atlas.
+    pub fn register_pipeline(
+        &mut self,
+        pipelines: &mut PipelineRegistry,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        subpass: u32,
+    ) {
+        // egui does not keep a consistent winding order.
+        let desc = PipelineDesc {
+            cull_mode: CullMode::None,
+            blend_mode: BlendMode::PremultipliedAlpha,
+            dynamic_scissor: true,
+            .. PipelineDesc::default()
+        };
+        let id = pipelines.get_or_create("gui", &desc, render_pass, subpass, Self::create_graphics_pipeline);
+
+        self.font_layout = Some(pipelines.get(id).descriptor_set_layout(0)
+            .expect("gui fragment shader has no descriptor set")
+            .clone());
+        self.pipeline = Some(id);
+        self.create_font_set();
+    }
+
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult<SingleBufferDefinition<GuiVertex>> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+                .expect("failed to create gui vertex shader module!");
+
+        let frag_shader_module = fragment_shader::Shader::load(device.clone())
+                .expect("failed to create gui fragment shader module!");
+
+        desc.build(
+                device,
+                SingleBufferDefinition::<GuiVertex>::new(),
+                vert_shader_module.main_entry_point(),
+                frag_shader_module.main_entry_point(),
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
+    }
+
+    fn create_font_set(&mut self) {
+        let (layout, image) = match (&self.font_layout, &self.font_image) {
+            (Some(layout), Some(image)) => (layout.clone(), image),
+            _ => return,
+        };
+
+        self.font_set = Some(Arc::new(PersistentDescriptorSet::start(layout)
+            .add_sampled_image(image.clone(), self.sampler.clone()).expect("failed to bind font atlas")
+            .build().expect("failed to build gui descriptor set")
+        ));
+    }
+
+    // Returns true when egui wants the event for itself, e.g. a click on a window or
+    // typing into a text field, so the application can skip it.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factor = *scale_factor as f32;
+                false
+            },
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = egui::Modifiers {
+                    alt: state.alt(),
+                    ctrl: state.ctrl(),
+                    shift: state.shift(),
+                    mac_cmd: cfg!(target_os = "macos") && state.logo(),
+                    command: if cfg!(target_os = "macos") { state.logo() } else { state.ctrl() },
+                };
+                false
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_position = egui::pos2(
+                                            position.x as f32 / self.scale_factor,
+                                            position.y as f32 / self.scale_factor
+                                        );
+                self.input.events.push(egui::Event::PointerMoved(self.pointer_position));
+                // Over a GUI window, so hover highlights get redrawn.
+                self.context.wants_pointer_input()
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.input.events.push(egui::Event::PointerGone);
+                false
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Other(_) => return false,
+                };
+                self.input.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_position,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+                self.context.wants_pointer_input()
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => egui::vec2(*x, *y) * SCROLL_LINE_HEIGHT,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        egui::vec2(position.x as f32, position.y as f32) / self.scale_factor
+                    },
+                };
+                self.input.scroll_delta += delta;
+                self.context.wants_pointer_input()
+            },
+            WindowEvent::ReceivedCharacter(character) => {
+                if !character.is_control() {
+                    self.input.events.push(egui::Event::Text(character.to_string()));
+                }
+                self.context.wants_keyboard_input()
+            },
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode.and_then(translate_key) {
+                    self.input.events.push(egui::Event::Key {
+                        key,
+                        pressed: input.state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+                self.context.wants_keyboard_input()
+            },
+            _ => false,
+        }
+    }
+
+    // Lays out the UI for this frame and uploads the font atlas when egui changed it.
+    pub fn run<F>(&mut self, window: &Window, uploader: &mut Uploader, build_ui: F)
+    where
+        F: FnOnce(&egui::CtxRef),
+    {
+        let size = window.inner_size();
+        self.scale_factor = window.scale_factor() as f32;
+        self.screen_size = [
+            size.width as f32 / self.scale_factor,
+            size.height as f32 / self.scale_factor,
+        ];
+
+        let mut input = std::mem::take(&mut self.input);
+        input.screen_rect = Some(egui::Rect::from_min_size(
+                                    egui::Pos2::ZERO,
+                                    egui::vec2(self.screen_size[0], self.screen_size[1])
+                                ));
+        input.pixels_per_point = Some(self.scale_factor);
+        input.time = Some(self.start.elapsed().as_secs_f64());
+        input.modifiers = self.modifiers;
+
+        self.context.begin_frame(input);
+        build_ui(&self.context);
+        let (output, shapes) = self.context.end_frame();
+        self.needs_repaint = output.needs_repaint;
+        self.meshes = self.context.tessellate(shapes);
+
+        let texture = self.context.texture();
+        if self.font_version != Some(texture.version) {
+            let dimensions = [texture.width as u32, texture.height as u32];
+            self.font_image = Some(uploader.upload_image(texture.pixels.iter().cloned(), dimensions, Format::R8Unorm));
+            self.font_version = Some(texture.version);
+            self.create_font_set();
+        }
+    }
+
+    // True when the last `run` asked for another frame, e.g. while a widget animates.
+    pub fn needs_repaint(&self) -> bool {
+        self.needs_repaint
+    }
+
+    pub fn record_draw(&mut self, builder: &mut AutoCommandBufferBuilder, pipelines: &PipelineRegistry) {
+        let pipeline = pipelines.get(self.pipeline.expect("gui pipeline not registered"));
+        let font_set = match &self.font_set {
+            Some(font_set) => font_set.clone(),
+            None => return,
+        };
+
+        let push_constants = vertex_shader::ty::PushConstants {
+            screen_size: self.screen_size,
+        };
+
+        for egui::ClippedMesh(clip, mesh) in self.meshes.drain(..) {
+            if mesh.indices.is_empty() {
+                continue;
+            }
+
+            // Only the font atlas is supported, user textures are skipped.
+            if mesh.texture_id != egui::TextureId::Egui {
+                continue;
+            }
+
+            let scissor = match clip_scissor(clip, self.scale_factor, self.screen_size) {
+                Some(scissor) => scissor,
+                None => continue,
+            };
+            let dynamic_state = DynamicState {
+                scissors: Some(vec![scissor]),
+                .. DynamicState::none()
+            };
+
+            let vertices = self.vertex_pool.chunk(mesh.vertices.iter().map(|vertex| GuiVertex {
+                    position: [vertex.pos.x, vertex.pos.y],
+                    uv: [vertex.uv.x, vertex.uv.y],
+                    color: [
+                        vertex.color.r() as f32 / 255.0,
+                        vertex.color.g() as f32 / 255.0,
+                        vertex.color.b() as f32 / 255.0,
+                        vertex.color.a() as f32 / 255.0,
+                    ],
+                })).expect("failed to allocate gui vertex buffer");
+            let indices = self.index_pool.chunk(mesh.indices.iter().cloned())
+                .expect("failed to allocate gui index buffer");
+
+            builder.draw_indexed(
+                        pipeline.clone(),
+                        &dynamic_state,
+                        Arc::new(vertices),
+                        Arc::new(indices),
+                        font_set.clone(),
+                        push_constants
+                    ).expect("failed to draw gui");
+        }
+    }
+}
+
+// Converts a clip rectangle in points into a scissor in pixels, clamped to the screen.
+fn clip_scissor(clip: egui::Rect, scale_factor: f32, screen_size: [f32; 2]) -> Option<Scissor> {
+    let min_x = (clip.min.x.max(0.0) * scale_factor).round();
+    let min_y = (clip.min.y.max(0.0) * scale_factor).round();
+    let max_x = (clip.max.x.min(screen_size[0]) * scale_factor).round();
+    let max_y = (clip.max.y.min(screen_size[1]) * scale_factor).round();
+
+    if max_x <= min_x || max_y <= min_y {
+        return None
+    }
+
+    Some(Scissor {
+        origin: [min_x as i32, min_y as i32],
+        dimensions: [(max_x - min_x) as u32, (max_y - min_y) as u32],
+    })
+}
+
+fn translate_key(key: VirtualKeyCode) -> Option<egui::Key> {
+    let key = match key {
+        VirtualKeyCode::Down => egui::Key::ArrowDown,
+        VirtualKeyCode::Left => egui::Key::ArrowLeft,
+        VirtualKeyCode::Right => egui::Key::ArrowRight,
+        VirtualKeyCode::Up => egui::Key::ArrowUp,
+        VirtualKeyCode::Escape => egui::Key::Escape,
+        VirtualKeyCode::Tab => egui::Key::Tab,
+        VirtualKeyCode::Back => egui::Key::Backspace,
+        VirtualKeyCode::Return => egui::Key::Enter,
+        VirtualKeyCode::Space => egui::Key::Space,
+        VirtualKeyCode::Insert => egui::Key::Insert,
+        VirtualKeyCode::Delete => egui::Key::Delete,
+        VirtualKeyCode::Home => egui::Key::Home,
+        VirtualKeyCode::End => egui::Key::End,
+        VirtualKeyCode::PageUp => egui::Key::PageUp,
+        VirtualKeyCode::PageDown => egui::Key::PageDown,
+        VirtualKeyCode::A => egui::Key::A,
+        VirtualKeyCode::C => egui::Key::C,
+        VirtualKeyCode::V => egui::Key::V,
+        VirtualKeyCode::X => egui::Key::X,
+        VirtualKeyCode::Z => egui::Key::Z,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(min: [f32; 2], max: [f32; 2]) -> egui::Rect {
+        egui::Rect::from_min_max(egui::pos2(min[0], min[1]), egui::pos2(max[0], max[1]))
+    }
+
+    #[test]
+    fn scissor_inside_the_screen() {
+        let scissor = clip_scissor(rect([10.0, 20.0], [110.0, 70.0]), 1.0, [800.0, 600.0]).unwrap();
+        assert_eq!(scissor.origin, [10, 20]);
+        assert_eq!(scissor.dimensions, [100, 50]);
+    }
+
+    #[test]
+    fn scissor_scaled_to_pixels() {
+        let scissor = clip_scissor(rect([10.0, 20.0], [110.0, 70.0]), 2.0, [800.0, 600.0]).unwrap();
+        assert_eq!(scissor.origin, [20, 40]);
+        assert_eq!(scissor.dimensions, [200, 100]);
+    }
+
+    #[test]
+    fn scissor_rounds_fractional_pixels() {
+        let scissor = clip_scissor(rect([0.3, 0.6], [10.4, 10.6]), 1.5, [800.0, 600.0]).unwrap();
+        assert_eq!(scissor.origin, [0, 1]);
+        assert_eq!(scissor.dimensions, [16, 15]);
+    }
+
+    #[test]
+    fn scissor_clamped_to_the_screen() {
+        let scissor = clip_scissor(rect([-50.0, -10.0], [900.0, 700.0]), 1.0, [800.0, 600.0]).unwrap();
+        assert_eq!(scissor.origin, [0, 0]);
+        assert_eq!(scissor.dimensions, [800, 600]);
+    }
+
+    #[test]
+    fn scissor_outside_the_screen_is_skipped() {
+        assert!(clip_scissor(rect([900.0, 10.0], [1000.0, 50.0]), 1.0, [800.0, 600.0]).is_none());
+        assert!(clip_scissor(rect([10.0, -80.0], [50.0, -10.0]), 1.0, [800.0, 600.0]).is_none());
+    }
+
+    #[test]
+    fn empty_clip_is_skipped() {
+        assert!(clip_scissor(rect([10.0, 10.0], [10.0, 50.0]), 1.0, [800.0, 600.0]).is_none());
+        assert!(clip_scissor(rect([10.0, 10.0], [50.0, 10.2]), 1.0, [800.0, 600.0]).is_none());
+    }
+}
@@ -0,0 +1,328 @@
+use winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use std::collections::{HashMap, HashSet};
+
+// Pixel scroll deltas from touchpads are converted to lines with this many pixels per line.
+const PIXELS_PER_SCROLL_LINE: f32 = 120.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    ToggleFullscreen,
+    NewWindow,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+// Keyboard and mouse state gathered from the window events of the current frame.
+// Held keys and buttons stay pressed across frames, while cursor movement, scrolling and
+// presses are accumulated until `end_frame`, so short taps between two frames are not lost.
+pub struct InputState {
+    pressed: HashSet<Binding>,
+    just_pressed: HashSet<Binding>,
+    // In physical pixels, `None` while the cursor is outside the window.
+    cursor_position: Option<[f32; 2]>,
+    cursor_delta: [f32; 2],
+    scroll_delta: [f32; 2],
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        let mut input = Self {
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            cursor_position: None,
+            cursor_delta: [0.0, 0.0],
+            scroll_delta: [0.0, 0.0],
+            bindings: HashMap::new(),
+        };
+
+        input.bind(Action::Quit, Binding::Key(VirtualKeyCode::Escape));
+        input.bind(Action::ToggleFullscreen, Binding::Key(VirtualKeyCode::F11));
+        input.bind(Action::NewWindow, Binding::Key(VirtualKeyCode::F2));
+        input.bind(Action::MoveForward, Binding::Key(VirtualKeyCode::W));
+        input.bind(Action::MoveBack, Binding::Key(VirtualKeyCode::S));
+        input.bind(Action::MoveLeft, Binding::Key(VirtualKeyCode::A));
+        input.bind(Action::MoveRight, Binding::Key(VirtualKeyCode::D));
+        input.bind(Action::MoveUp, Binding::Key(VirtualKeyCode::E));
+        input.bind(Action::MoveDown, Binding::Key(VirtualKeyCode::Q));
+
+        input
+    }
+
+    // Adds `binding` to the ones that trigger `action`, an action can have several.
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { input: KeyboardInput { virtual_keycode: Some(key), state, .. }, .. } => {
+                self.set_pressed(Binding::Key(*key), *state);
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.set_pressed(Binding::Mouse(*button), *state);
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = [position.x as f32, position.y as f32];
+                if let Some(last) = self.cursor_position {
+                    self.cursor_delta[0] += position[0] - last[0];
+                    self.cursor_delta[1] += position[1] - last[1];
+                }
+                self.cursor_position = Some(position);
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let [x, y] = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => [*x, *y],
+                    MouseScrollDelta::PixelDelta(position) => [
+                        position.x as f32 / PIXELS_PER_SCROLL_LINE,
+                        position.y as f32 / PIXELS_PER_SCROLL_LINE,
+                    ],
+                };
+                self.scroll_delta[0] += x;
+                self.scroll_delta[1] += y;
+            },
+            // Releases are not delivered to unfocused windows.
+            WindowEvent::Focused(false) => {
+                self.pressed.clear();
+            },
+            _ => (),
+        }
+    }
+
+    fn set_pressed(&mut self, binding: Binding, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                // Key repeats arrive as more presses without a release in between.
+                if self.pressed.insert(binding) {
+                    self.just_pressed.insert(binding);
+                }
+            },
+            ElementState::Released => {
+                self.pressed.remove(&binding);
+            },
+        }
+    }
+
+    // Clears everything accumulated during the frame. Called once the frame's update has
+    // consumed the input.
+    pub fn end_frame(&mut self) {
+        self.just_pressed.clear();
+        self.cursor_delta = [0.0, 0.0];
+        self.scroll_delta = [0.0, 0.0];
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.pressed.contains(&Binding::Mouse(button))
+    }
+
+    pub fn cursor_delta(&self) -> [f32; 2] {
+        self.cursor_delta
+    }
+
+    // In lines, positive Y scrolls up.
+    pub fn scroll_delta(&self) -> [f32; 2] {
+        self.scroll_delta
+    }
+
+    // True while any binding of `action` is held.
+    pub fn is_action_pressed(&self, action: Action) -> bool {
+        self.bindings_of(action).any(|binding| self.pressed.contains(binding))
+    }
+
+    // True when a binding of `action` was pressed during this frame.
+    pub fn was_action_triggered(&self, action: Action) -> bool {
+        self.bindings_of(action).any(|binding| self.just_pressed.contains(binding))
+    }
+
+    // -1, 0 or 1 depending on which of two opposite actions are held.
+    pub fn axis(&self, positive: Action, negative: Action) -> f32 {
+        self.is_action_pressed(positive) as i32 as f32 - self.is_action_pressed(negative) as i32 as f32
+    }
+
+    fn bindings_of(&self, action: Action) -> impl Iterator<Item = &Binding> {
+        self.bindings.get(&action).into_iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use winit::dpi::PhysicalPosition;
+    use winit::event::{DeviceId, ModifiersState, TouchPhase};
+
+    // Device ids only exist to tell devices apart, the input state ignores them.
+    fn device_id() -> DeviceId {
+        unsafe { DeviceId::dummy() }
+    }
+
+    #[allow(deprecated)]
+    fn key(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            device_id: device_id(),
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn mouse(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::MouseInput {
+            device_id: device_id(),
+            state,
+            button,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn cursor(x: f64, y: f64) -> WindowEvent<'static> {
+        WindowEvent::CursorMoved {
+            device_id: device_id(),
+            position: PhysicalPosition::new(x, y),
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn scroll(delta: MouseScrollDelta) -> WindowEvent<'static> {
+        WindowEvent::MouseWheel {
+            device_id: device_id(),
+            delta,
+            phase: TouchPhase::Moved,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut input = InputState::new();
+
+        input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(input.is_action_pressed(Action::MoveForward));
+        assert!(input.was_action_triggered(Action::MoveForward));
+
+        input.handle_event(&key(VirtualKeyCode::W, ElementState::Released));
+        assert!(!input.is_action_pressed(Action::MoveForward));
+        // The press still happened during this frame.
+        assert!(input.was_action_triggered(Action::MoveForward));
+    }
+
+    #[test]
+    fn repeat_does_not_trigger_again() {
+        let mut input = InputState::new();
+
+        input.handle_event(&key(VirtualKeyCode::Escape, ElementState::Pressed));
+        input.end_frame();
+        input.handle_event(&key(VirtualKeyCode::Escape, ElementState::Pressed));
+
+        assert!(input.is_action_pressed(Action::Quit));
+        assert!(!input.was_action_triggered(Action::Quit));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything() {
+        let mut input = InputState::new();
+        input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        input.handle_event(&mouse(MouseButton::Left, ElementState::Pressed));
+
+        input.handle_event(&WindowEvent::Focused(false));
+
+        assert!(!input.is_action_pressed(Action::MoveForward));
+        assert!(!input.is_button_pressed(MouseButton::Left));
+    }
+
+    #[test]
+    fn end_frame_resets_the_frame_state() {
+        let mut input = InputState::new();
+        input.handle_event(&key(VirtualKeyCode::F11, ElementState::Pressed));
+        input.handle_event(&cursor(10.0, 10.0));
+        input.handle_event(&cursor(15.0, 8.0));
+        input.handle_event(&scroll(MouseScrollDelta::LineDelta(0.0, 1.0)));
+
+        assert!(input.was_action_triggered(Action::ToggleFullscreen));
+        assert_eq!(input.cursor_delta(), [5.0, -2.0]);
+        assert_eq!(input.scroll_delta(), [0.0, 1.0]);
+
+        input.end_frame();
+
+        assert!(!input.was_action_triggered(Action::ToggleFullscreen));
+        assert!(input.is_action_pressed(Action::ToggleFullscreen));
+        assert_eq!(input.cursor_delta(), [0.0, 0.0]);
+        assert_eq!(input.scroll_delta(), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn cursor_delta_starts_after_entering() {
+        let mut input = InputState::new();
+        input.handle_event(&cursor(100.0, 100.0));
+        assert_eq!(input.cursor_delta(), [0.0, 0.0]);
+
+        input.handle_event(&WindowEvent::CursorLeft { device_id: device_id() });
+        input.handle_event(&cursor(0.0, 0.0));
+        assert_eq!(input.cursor_delta(), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn pixel_scroll_converted_to_lines() {
+        let mut input = InputState::new();
+        input.handle_event(&scroll(MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.0, 240.0))));
+
+        assert_eq!(input.scroll_delta(), [0.0, 2.0]);
+    }
+
+    #[test]
+    fn axis_of_opposite_actions() {
+        let mut input = InputState::new();
+        assert_eq!(input.axis(Action::MoveRight, Action::MoveLeft), 0.0);
+
+        input.handle_event(&key(VirtualKeyCode::D, ElementState::Pressed));
+        assert_eq!(input.axis(Action::MoveRight, Action::MoveLeft), 1.0);
+
+        input.handle_event(&key(VirtualKeyCode::A, ElementState::Pressed));
+        assert_eq!(input.axis(Action::MoveRight, Action::MoveLeft), 0.0);
+
+        input.handle_event(&key(VirtualKeyCode::D, ElementState::Released));
+        assert_eq!(input.axis(Action::MoveRight, Action::MoveLeft), -1.0);
+    }
+
+    #[test]
+    fn actions_can_have_several_bindings() {
+        let mut input = InputState::new();
+        input.bind(Action::MoveForward, Binding::Key(VirtualKeyCode::Up));
+        input.bind(Action::MoveForward, Binding::Key(VirtualKeyCode::Up));
+
+        input.handle_event(&key(VirtualKeyCode::Up, ElementState::Pressed));
+        assert!(input.is_action_pressed(Action::MoveForward));
+        assert_eq!(input.bindings_of(Action::MoveForward).count(), 2);
+    }
+}
@@ -1,11 +1,42 @@
-use winit::event_loop::{EventLoop,ControlFlow};
-use winit::window::{WindowBuilder, Window};
+mod swap_chain;
+mod frame_pacer;
+mod frame_stats;
+mod gpu_queries;
+mod device_requirements;
+mod uploader;
+mod compute;
+mod computed_vertices;
+mod particles;
+mod shader_reflect;
+mod runtime_shaders;
+mod pipeline_cache;
+mod pipeline_desc;
+mod pipeline_registry;
+mod render_queue;
+mod debug_draw;
+mod gui;
+mod text;
+mod camera;
+mod input;
+mod window_mode;
+mod window_context;
+
+use winit::event_loop::{EventLoop,EventLoopProxy,EventLoopWindowTarget,ControlFlow};
+use winit::window::{WindowBuilder, Window, WindowId};
 use winit::dpi::LogicalSize;
 use winit::event::Event;
-use winit::event::WindowEvent;
+use winit::event::{ElementState, KeyboardInput, WindowEvent};
 
 use std::sync::Arc;
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::iter;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
+
+use cgmath::Point3;
 
 use vulkano_win::VkSurfaceBuild;
 
@@ -34,10 +65,6 @@ use vulkano::device::{
 
 use vulkano::swapchain::{
         Surface,
-        Capabilities,
-        ColorSpace,
-        SupportedPresentModes,
-        PresentMode,
         Swapchain,
         CompositeAlpha,
         FullscreenExclusive,
@@ -52,10 +79,9 @@ use vulkano::image::{ImageUsage, swapchain::SwapchainImage};
 use vulkano::sync::{self, SharingMode,GpuFuture};
 
 use vulkano::pipeline::{
-        GraphicsPipeline,
         vertex::BufferlessDefinition,
         vertex::BufferlessVertices,
-        viewport::Viewport,
+        cache::PipelineCache,
     };
 
 use vulkano::framebuffer::{
@@ -66,80 +92,265 @@ use vulkano::framebuffer::{
     };
 
 use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+
+use vulkano::buffer::CpuBufferPool;
 
 use vulkano::command_buffer::{
-        AutoCommandBuffer,
         AutoCommandBufferBuilder,
+        CommandBuffer,
         DynamicState,
         SubpassContents,
     };
-
-use vulkano::single_pass_renderpass;
-
+use vulkano::command_buffer::pool::standard::StandardCommandPoolAlloc;
+
+use vulkano::ordered_passes_renderpass;
+
+use frame_pacer::{FramePacer, RenderMode};
+use frame_stats::{FrameStats, FrameSummary, FrameTiming, add_gpu};
+use gpu_queries::{FrameFence, GpuTimer, SceneCounts, SceneQueries};
+use device_requirements::DeviceRequirements;
+use uploader::Uploader;
+use computed_vertices::ComputedVertices;
+use particles::ParticleSystem;
+use runtime_shaders::{RuntimeShaderProgram, ShaderDefines, ShaderError, ShaderWatcher};
+use pipeline_cache::PipelineCacheFile;
+use pipeline_desc::{BlendMode, PipelineDesc};
+use pipeline_registry::{Pipeline, PipelineId, PipelineRegistry, PipelineResult};
+use render_queue::{RenderQueue, view_depth};
+use debug_draw::DebugDraw;
+use gui::GuiOverlay;
+use text::TextRenderer;
+use camera::{Camera, CameraController, CameraUniforms, ControllerKind, Projection};
+use input::{Action, InputState};
+use window_mode::WindowMode;
+use window_context::{WindowContext, WindowError, window_extent};
+
+const WINDOW_TITLE: &str = "Vulkan";
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 600;
 
+const WINDOW_MODE: WindowMode = WindowMode::Windowed;
+// The mode the fullscreen toggle switches to from a window.
+const FULLSCREEN_MODE: WindowMode = WindowMode::Borderless;
+// Additional windows showing the scene, driven by the same device. More can be opened
+// at runtime with the NewWindow action.
+const EXTRA_WINDOWS: u32 = 0;
+
+const TARGET_FPS: Option<u32> = Some(60);
+const RENDER_MODE: RenderMode = RenderMode::Continuous;
+
+const FRAME_STATS_SAMPLES: usize = 240;
+const FRAME_STATS_IN_TITLE: bool = true;
+// Environment variable naming a CSV file that receives the timing of every frame.
+const FRAME_STATS_CSV_VAR: &str = "FRAME_STATS_CSV";
+// Measures the GPU time of every frame with timestamp queries when the graphics queue
+// supports them.
+const GPU_TIMESTAMPS: bool = true;
+// Counts the samples and the pipeline work of the primary window's scene with occlusion
+// and pipeline statistics queries.
+const SCENE_QUERIES: bool = false;
+
+const COMPUTE_VERTICES: bool = false;
+const PARTICLES: bool = false;
+const PARTICLE_COUNT: u32 = 16384;
+
+const RUNTIME_SHADERS: bool = false;
+const VERTEX_SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader_base.vert");
+const FRAGMENT_SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader_base.frag");
+// Builds the scene shaders with PALETTE_COLORS defined, which colours the vertices from
+// the shared palette instead of pure red, green and blue.
+const PALETTE_COLORS: bool = false;
+
+const BLEND_MODE: BlendMode = BlendMode::Opaque;
+// Centroid of the triangle in `shader_base.vert`, in world space.
+const TRIANGLE_CENTER: [f32; 3] = [0.0, -1.0 / 6.0, 0.0];
+const WIREFRAME: bool = false;
+
+const DEBUG_DRAW: bool = false;
+
+const GUI: bool = false;
+
+const TEXT: bool = false;
+const TEXT_FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+const TEXT_PIXEL_SIZE: f32 = 18.0;
+
+const CAMERA_POSITION: [f32; 3] = [0.0, 0.0, 2.5];
+const CAMERA_PROJECTION: Projection = Projection::Perspective { fov_y: 45.0, near: 0.1, far: 100.0 };
+const CAMERA_CONTROLLER: Option<ControllerKind> = None;
+
+const SCENE_SUBPASS: u32 = 0;
+const OVERLAY_SUBPASS: u32 = 1;
+
 const VALIDATION_LAYERS: &[&str] = &[
 ];
 
-fn device_extensions() -> DeviceExtensions {
-    DeviceExtensions {
-        khr_swapchain: true,
-        .. vulkano::device::DeviceExtensions::none()
+// `instance_extensions` are the extensions the instance was created with.
+fn device_requirements(instance_extensions: &InstanceExtensions) -> DeviceRequirements {
+    DeviceRequirements {
+        required_features: Features::none(),
+        optional_features: Features {
+            sampler_anisotropy: true,
+            fill_mode_non_solid: true,
+            wide_lines: true,
+            // Scene queries, which need all three to count exactly.
+            inherited_queries: true,
+            pipeline_statistics_query: true,
+            occlusion_query_precise: true,
+            .. Features::none()
+        },
+        required_extensions: DeviceExtensions {
+            khr_swapchain: true,
+            .. DeviceExtensions::none()
+        },
+        optional_extensions: DeviceExtensions {
+            // Depends on both instance extensions being enabled.
+            ext_full_screen_exclusive: instance_extensions.khr_get_surface_capabilities2
+                && instance_extensions.khr_get_physical_device_properties2,
+            .. DeviceExtensions::none()
+        },
     }
 }
 
-#[cfg(all(debug_assertions))]
+#[cfg(debug_assertions)]
 const ENABLE_VALIDATION_LAYERS: bool = true;
 #[cfg(not(debug_assertions))]
 const ENABLE_VALIDATION_LAYERS: bool = false;
 
 struct QueueFamilyIndices {
-    graphics_family: i32,
-    present_family: i32,
+    graphics_family: Option<u32>,
+    present_family: Option<u32>,
+    transfer_family: Option<u32>,
+    compute_family: Option<u32>,
 }
 
 impl QueueFamilyIndices {
     fn new() -> Self {
-        Self { graphics_family: -1, present_family: -1 }
+        Self {
+            graphics_family: None,
+            present_family: None,
+            transfer_family: None,
+            compute_family: None,
+        }
     }
 
     fn is_complete(&self) -> bool {
-        self.graphics_family >= 0 && self.present_family >= 0
+        self.graphics_family.is_some() && self.present_family.is_some()
+    }
+
+    fn unique_families(&self) -> Vec<u32> {
+        let mut families = Vec::new();
+        let candidates = [self.graphics_family, self.present_family, self.transfer_family, self.compute_family];
+        for family in candidates.iter().flatten() {
+            if !families.contains(family) {
+                families.push(*family);
+            }
+        }
+
+        families
     }
 }
 
-type ConcreteGraphicsPipeline = GraphicsPipeline<
-                                    BufferlessDefinition, 
-                                    Box<dyn PipelineLayoutAbstract + Send + Sync + 'static>, 
-                                    Arc<dyn RenderPassAbstract + Send + Sync + 'static>
-                                >;
+struct DeviceQueues {
+    graphics: Arc<Queue>,
+    transfer: Arc<Queue>,
+    compute: Arc<Queue>,
+    // One queue per family the device was created with, including the present family.
+    all: Vec<Arc<Queue>>,
+}
+
+enum FrameDraw {
+    Scene,
+    Particles,
+}
+
+// A variant of the scene shaders, registered under its own names with the defines its
+// shaders are built with.
+struct ShaderVariant {
+    name: &'static str,
+    runtime_name: &'static str,
+    defines: ShaderDefines<'static>,
+}
+
+// Declares each variant's compiled vertex shader and its runtime defines from the same
+// list, so both ways of building the scene see the same macros.
+macro_rules! scene_shader_variants {
+    ($($variant:ident: $name:literal => $module:ident [$(($define:tt, $value:tt)),*],)*) => {
+        $(
+            mod $module {
+                vulkano_shaders::shader! {
+                    ty: "vertex",
+                    path: "src/shader_base.vert",
+                    include: ["src"],
+                    define: [$(($define, $value)),*]
+                }
+            }
+
+            const $variant: ShaderVariant = ShaderVariant {
+                name: $name,
+                runtime_name: concat!("runtime_", $name),
+                defines: &[$(($define, Some($value))),*],
+            };
+        )*
+    };
+}
+
+scene_shader_variants! {
+    BASE_SHADERS: "base" => vertex_shader [],
+    PALETTE_SHADERS: "palette" => palette_vertex_shader [("PALETTE_COLORS", "")],
+}
+
+const SCENE_SHADERS: ShaderVariant = if PALETTE_COLORS { PALETTE_SHADERS } else { BASE_SHADERS };
+
+type ConcreteGraphicsPipeline = Pipeline<BufferlessDefinition>;
+// A frame recorded with vulkano's builder, or with the queries around the scene.
+type FrameCommandBuffer = Arc<dyn CommandBuffer<PoolAlloc = StandardCommandPoolAlloc> + Send + Sync>;
+
+const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
 
 struct HelloTriangleApplication {
     instance: Arc<Instance>,
     #[allow(unused)]
     debug_callback: Option<DebugCallback>,
 
-    surface: Arc<Surface<Window>>,
+    // Shared so a window can be borrowed while the application draws into it.
+    windows: HashMap<WindowId, Rc<RefCell<WindowContext>>>,
+    // Compute work, particles and overlays only draw into the primary window, and
+    // closing it quits.
+    primary_window: WindowId,
+    focused_window: WindowId,
 
     physical_device_index: usize,
     device: Arc<Device>,
 
     graphics_queue: Arc<Queue>,
-    present_queue: Arc<Queue>,
+    // Searched for a queue that can present to each new window.
+    queues: Vec<Arc<Queue>>,
+
+    pipeline_cache: PipelineCacheFile,
 
-    swap_chain: Arc<Swapchain<Window>>,
-    swap_chain_images: Vec<Arc<SwapchainImage<Window>>>,
+    runtime_shaders: Option<Rc<RuntimeShaderProgram>>,
+    shader_watcher: Option<ShaderWatcher>,
 
-    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
-    graphics_pipeline: Arc<ConcreteGraphicsPipeline>,
+    computed_vertices: Option<ComputedVertices>,
+    particles: Option<ParticleSystem>,
+    debug_draw: Option<DebugDraw>,
+    gui: Option<GuiOverlay>,
+    text: Option<TextRenderer>,
+    gui_target_fps: u32,
+    last_frame_summary: Option<FrameSummary>,
+    last_scene_counts: Option<SceneCounts>,
 
-    swap_chain_framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    input: InputState,
+    camera: Camera,
+    camera_controller: Option<CameraController>,
+    camera_uniforms: CpuBufferPool<CameraUniforms>,
+    last_camera_update: Instant,
 
-    command_buffers: Vec<Arc<AutoCommandBuffer>>,
+    uploader: Uploader,
 
-    previous_frame_end: Option<Box<dyn GpuFuture>>,
-    recreate_swap_chain: bool,
+    frame_pacer: FramePacer,
+    frame_stats: FrameStats,
 }
 
 impl HelloTriangleApplication {
@@ -147,72 +358,290 @@ impl HelloTriangleApplication {
         let instance = Self::create_instance();
         let debug_callback = Self::setup_debug_callback(&instance);
 
-        let surface = Self::create_surface_exterior_evl(&instance, &events_loop);
+        let surface = Self::create_surface_exterior_evl(&instance, events_loop, WINDOW_TITLE, WINDOW_MODE);
+        let primary_window = surface.window().id();
 
         let physical_device_index = Self::pick_physical_device(&instance, &surface);
-        let (device, graphics_queue, present_queue) = Self::create_logical_device(
+        let (device, queues) = Self::create_logical_device(
                     &instance, &surface, physical_device_index);
+        let DeviceQueues {
+            graphics: graphics_queue,
+            transfer: transfer_queue,
+            compute: compute_queue,
+            all: queues,
+        } = queues;
 
-        let (swap_chain, swap_chain_images) = Self::create_swap_chain(
-                                                &instance,
-                                                &surface,
-                                                physical_device_index,
-                                                &device,
-                                                &graphics_queue,
-                                                &present_queue,
-                                                None
-                                            );
+        let pipeline_cache = PipelineCacheFile::load(&device);
 
-        let render_pass = Self::create_render_pass(&device, swap_chain.format());
-        let graphics_pipeline = Self::create_graphics_pipeline(
-                                        &device, 
-                                        swap_chain.dimensions(),
-                                        &render_pass
-                                    );
+        let computed_vertices = if COMPUTE_VERTICES {
+            Some(ComputedVertices::new(&device, &compute_queue, &graphics_queue, pipeline_cache.cache()))
+        } else {
+            None
+        };
+
+        let (runtime_shaders, shader_watcher) = Self::create_runtime_shaders(&device, events_loop.create_proxy());
+
+        let mut uploader = Uploader::new(&device, &transfer_queue, &[&graphics_queue, &compute_queue]);
+
+        let particles = if PARTICLES {
+            Some(ParticleSystem::new(
+                    &device,
+                    &mut uploader,
+                    pipeline_cache.cache(),
+                    PARTICLE_COUNT
+                ))
+        } else {
+            None
+        };
+
+        let debug_draw = if DEBUG_DRAW {
+            Some(DebugDraw::new(&device))
+        } else {
+            None
+        };
+
+        let gui = if GUI {
+            Some(GuiOverlay::new(&device, surface.window()))
+        } else {
+            None
+        };
 
-        let swap_chain_framebuffers = Self::create_framebuffers(&swap_chain_images, &render_pass);
+        let text = Self::create_text_renderer(&device, &mut uploader);
 
-        let previous_frame_end = Some(Self::create_sync_objects(&device));
+        let mut camera = Camera::new(Point3::from(CAMERA_POSITION), CAMERA_PROJECTION);
+        let camera_controller = CAMERA_CONTROLLER.map(|kind| CameraController::new(kind, &mut camera));
+        let camera_uniforms = CpuBufferPool::uniform_buffer(device.clone());
+
+        let frame_stats = Self::create_frame_stats();
 
         let mut app = Self {
             instance,
             debug_callback,
 
-            // events_loop,
-            surface,
+            windows: HashMap::new(),
+            primary_window,
+            focused_window: primary_window,
 
             physical_device_index,
             device,
 
             graphics_queue,
+            queues,
+
+            pipeline_cache,
+
+            runtime_shaders,
+            shader_watcher,
+
+            computed_vertices,
+            particles,
+            debug_draw,
+            gui,
+            text,
+            gui_target_fps: TARGET_FPS.unwrap_or(60),
+            last_frame_summary: None,
+            last_scene_counts: None,
+
+            input: InputState::new(),
+            camera,
+            camera_controller,
+            camera_uniforms,
+            last_camera_update: Instant::now(),
+
+            uploader,
+
+            frame_pacer: FramePacer::new(TARGET_FPS, RENDER_MODE),
+            frame_stats,
+        };
+
+        let mut primary = app.create_window_context(surface, WINDOW_MODE)
+            .unwrap_or_else(|err| panic!("failed to set up the window: {}", err));
+        app.register_primary_pipelines(&mut primary);
+        app.add_window(primary);
+
+        // EXTRA_WINDOWS may be zero.
+        #[allow(clippy::reversed_empty_ranges)]
+        for _ in 0..EXTRA_WINDOWS {
+            app.open_window(events_loop);
+        }
+
+        app
+    }
+
+    // The device was picked for the primary window's surface, so it is checked against
+    // the surface of any other window.
+    fn create_window_context(&mut self, surface: Arc<Surface<Window>>, window_mode: WindowMode) -> Result<WindowContext, WindowError> {
+        let physical_device = PhysicalDevice::from_index(&self.instance, self.physical_device_index).unwrap();
+        if !Self::is_device_suitable(&surface, &physical_device) {
+            return Err(WindowError::UnsuitableDevice)
+        }
+
+        let present_queue = self.find_present_queue(&surface)
+            .ok_or(WindowError::NoPresentQueue)?;
+        let (swap_chain, swap_chain_images) = Self::create_swap_chain(
+                                                &self.instance,
+                                                &surface,
+                                                self.physical_device_index,
+                                                &self.device,
+                                                &self.graphics_queue,
+                                                &present_queue,
+                                                window_mode.fullscreen_exclusive(self.enabled_extensions()),
+                                                None
+                                            );
+
+        let render_pass = Self::create_render_pass(&self.device, swap_chain.format());
+        let mut pipelines = PipelineRegistry::new(&self.device, self.pipeline_cache.cache(), swap_chain.dimensions());
+        let primary = surface.window().id() == self.primary_window;
+        let main_pipeline = Self::create_main_pipeline(
+                                        &mut pipelines,
+                                        &Self::scene_pipeline_desc(self.enabled_features()),
+                                        &render_pass,
+                                        self.computed_vertices.as_mut().filter(|_| primary),
+                                        self.runtime_shaders.as_ref()
+                                    );
+
+        let framebuffers = Self::create_framebuffers(&swap_chain_images, &render_pass);
+        let gpu_timer = if GPU_TIMESTAMPS {
+            GpuTimer::new(&self.device, &self.graphics_queue, swap_chain_images.len())
+        } else {
+            None
+        };
+        let scene_queries = if SCENE_QUERIES && primary {
+            SceneQueries::new(&self.device, &self.graphics_queue, swap_chain_images.len())
+        } else {
+            None
+        };
+
+        Ok(WindowContext {
+            surface,
+            window_mode,
             present_queue,
 
             swap_chain,
             swap_chain_images,
 
             render_pass,
-            graphics_pipeline,
-
-            swap_chain_framebuffers,
+            pipelines,
+            main_pipeline,
 
+            framebuffers,
             command_buffers: vec![],
 
-            previous_frame_end,
+            previous_frame_end: Some(Self::create_sync_objects(&self.device)),
+            gpu_timer,
+            scene_queries,
             recreate_swap_chain: false,
+            minimized: false,
+        })
+    }
+
+    // Opens another window showing the scene. A window the device can't present to is
+    // closed again.
+    fn open_window(&mut self, window_target: &EventLoopWindowTarget<()>) {
+        let title = format!("{} {}", WINDOW_TITLE, self.windows.len() + 1);
+        let surface = Self::create_surface_exterior_evl(&self.instance, window_target, &title, WindowMode::Windowed);
+
+        match self.create_window_context(surface, WindowMode::Windowed) {
+            Ok(window) => self.add_window(window),
+            Err(err) => println!("failed to open window \"{}\": {}", title, err),
+        }
+    }
+
+    fn add_window(&mut self, mut window: WindowContext) {
+        self.create_command_buffers(&mut window);
+        self.windows.insert(window.id(), Rc::new(RefCell::new(window)));
+    }
+
+    // The graphics queue when it can present to `surface`, which keeps the swap chain
+    // images exclusive to one queue family, otherwise any queue of the device that can.
+    fn find_present_queue(&self, surface: &Arc<Surface<Window>>) -> Option<Arc<Queue>> {
+        iter::once(&self.graphics_queue).chain(&self.queues)
+            .find(|queue| surface.is_supported(queue.family()).unwrap_or(false))
+            .cloned()
+    }
+
+    // Handles to every window, for loops that call back into the application while a
+    // window is borrowed.
+    fn window_list(&self) -> Vec<Rc<RefCell<WindowContext>>> {
+        self.windows.values().cloned().collect()
+    }
+
+    // Pipelines of the passes that only draw into the primary window. They live in its
+    // registry, so they are rebuilt along with the scene pipeline.
+    fn register_primary_pipelines(&mut self, window: &mut WindowContext) {
+        if let Some(particles) = self.particles.as_mut() {
+            particles.register_pipeline(&mut window.pipelines, &window.render_pass, SCENE_SUBPASS);
+        }
+        if let Some(debug_draw) = self.debug_draw.as_mut() {
+            debug_draw.register_pipeline(&mut window.pipelines, &window.render_pass, SCENE_SUBPASS);
+        }
+        if let Some(gui) = self.gui.as_mut() {
+            gui.register_pipeline(&mut window.pipelines, &window.render_pass, OVERLAY_SUBPASS);
+        }
+        if let Some(text) = self.text.as_mut() {
+            text.register_pipeline(&mut window.pipelines, &window.render_pass, OVERLAY_SUBPASS);
+        }
+    }
+
+    fn create_runtime_shaders(device: &Arc<Device>, event_loop: EventLoopProxy<()>) -> (Option<Rc<RuntimeShaderProgram>>, Option<ShaderWatcher>) {
+        if !RUNTIME_SHADERS {
+            return (None, None)
+        }
+
+        let vertex_path = Path::new(VERTEX_SHADER_PATH);
+        let fragment_path = Path::new(FRAGMENT_SHADER_PATH);
+
+        let program = RuntimeShaderProgram::load(device, vertex_path, fragment_path, SCENE_SHADERS.defines)
+            .unwrap_or_else(|err| panic!("failed to load runtime shaders: {}", err));
+
+        let watcher = match ShaderWatcher::new(&program.dependencies(), event_loop) {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                println!("failed to watch shader files, hot reload disabled: {:?}", err);
+                None
+            }
         };
 
-        app.create_command_buffers();
-        app
+        (Some(Rc::new(program)), watcher)
     }
 
-    fn create_surface_exterior_evl(instance: &Arc<Instance>, events_loop: &EventLoop<()>) -> Arc<Surface<Window>> {
-        let surface = WindowBuilder::new()
-                        .with_title("Vulkan")
-                        .with_inner_size(LogicalSize::new(f64::from(WIDTH), f64::from(HEIGHT)))
-                        .build_vk_surface(events_loop, instance.clone())
-                        .expect("failed to create window surface!");
+    fn create_text_renderer(device: &Arc<Device>, uploader: &mut Uploader) -> Option<TextRenderer> {
+        if !TEXT {
+            return None
+        }
+
+        match TextRenderer::new(device, uploader, TEXT_FONT, TEXT_PIXEL_SIZE) {
+            Ok(text) => Some(text),
+            Err(err) => {
+                println!("{}, text rendering disabled", err);
+                None
+            }
+        }
+    }
+
+    fn create_frame_stats() -> FrameStats {
+        let mut frame_stats = FrameStats::new(FRAME_STATS_SAMPLES);
+        if let Some(path) = env::var_os(FRAME_STATS_CSV_VAR) {
+            if let Err(err) = frame_stats.export_csv(&path) {
+                println!("failed to create frame stats file {:?}: {:?}", path, err);
+            }
+        }
+
+        frame_stats
+    }
 
-        surface
+    fn create_surface_exterior_evl(
+        instance: &Arc<Instance>,
+        window_target: &EventLoopWindowTarget<()>,
+        title: &str,
+        window_mode: WindowMode,
+    ) -> Arc<Surface<Window>> {
+        WindowBuilder::new()
+                        .with_title(title)
+                        .with_inner_size(LogicalSize::new(f64::from(WIDTH), f64::from(HEIGHT)))
+                        .with_fullscreen(window_mode.fullscreen(window_target.primary_monitor()))
+                        .build_vk_surface(window_target, instance.clone())
+                        .expect("failed to create window surface!")
     }
 
     fn create_instance() -> Arc<Instance> {
@@ -232,7 +661,7 @@ impl HelloTriangleApplication {
             engine_version: Some(Version {major: 1, minor: 0, patch: 0}),
         };
 
-        let required_extensions = Self::get_required_extensions();
+        let required_extensions = Self::get_required_extensions(&supported_extensions);
 
         if ENABLE_VALIDATION_LAYERS && Self::check_validation_layer_support() {
             Instance::new(Some(&app_info), &required_extensions, VALIDATION_LAYERS.iter().cloned())
@@ -250,12 +679,16 @@ impl HelloTriangleApplication {
             .all(|layer_name| layers.contains(&layer_name.to_string()))
     }
 
-    fn get_required_extensions() -> InstanceExtensions {
+    fn get_required_extensions(supported_extensions: &InstanceExtensions) -> InstanceExtensions {
         let mut extensions = vulkano_win::required_extensions();
         if ENABLE_VALIDATION_LAYERS {
             extensions.ext_debug_utils = true;
         }
 
+        // Needed by the device's ext_full_screen_exclusive, only enabled when available.
+        extensions.khr_get_surface_capabilities2 = supported_extensions.khr_get_surface_capabilities2;
+        extensions.khr_get_physical_device_properties2 = supported_extensions.khr_get_physical_device_properties2;
+
         extensions
     }
 
@@ -277,16 +710,16 @@ impl HelloTriangleApplication {
             verbose: true,
         };
 
-        DebugCallback::new(&instance, msg_severity, msg_type, |msg| {
+        DebugCallback::new(instance, msg_severity, msg_type, |msg| {
             println!("validation layer: {:?}", msg.description);
         }).ok()
     }
 
     fn pick_physical_device(instance: &Arc<Instance>, surface: &Arc<Surface<Window>>) -> usize {
 
-        let selected = PhysicalDevice::enumerate(&instance)
-            .filter(|device| Self::rate_device_suitability(surface, &device).is_some())
-            .max_by_key(|device| Self::rate_device_suitability(surface, &device).unwrap())
+        let selected = PhysicalDevice::enumerate(instance)
+            .filter(|device| Self::rate_device_suitability(surface, device).is_some())
+            .max_by_key(|device| Self::rate_device_suitability(surface, device).unwrap())
             .expect("failed to find a suitable GPU!");
 
         println!("Physical device selected: {}", selected.name());
@@ -298,16 +731,13 @@ impl HelloTriangleApplication {
     fn rate_device_suitability(surface: &Arc<Surface<Window>>, device: &PhysicalDevice) -> Option<u32> {
         let mut score = 0;
 
-        match device.ty() {
-            PhysicalDeviceType::DiscreteGpu => {
-                score += 1000;
-            },
-            _ => {}
-        };
+        if device.ty() == PhysicalDeviceType::DiscreteGpu {
+            score += 1000;
+        }
 
         score += device.limits().max_image_dimension_2d();
 
-        if !Self::is_device_suitable(surface, &device) {
+        if !Self::is_device_suitable(surface, device) {
             None
         } else {
             Some(score)
@@ -317,9 +747,9 @@ impl HelloTriangleApplication {
 
     fn is_device_suitable(surface: &Arc<Surface<Window>>, device: &PhysicalDevice) -> bool {
         let indices = Self::find_queue_families(surface, device);
-        let extensions_supported = Self::check_device_extension_support(device);
+        let requirements_supported = device_requirements(&device.instance().loaded_extensions()).is_supported_by(device);
 
-        let swap_chain_adequate = if extensions_supported {
+        let swap_chain_adequate = if requirements_supported {
             let capabilities = surface.capabilities(*device)
                                 .expect("failed to get surface capabilities");
             !capabilities.supported_formats.is_empty() && 
@@ -328,46 +758,10 @@ impl HelloTriangleApplication {
             false
         };
 
-        indices.is_complete() && extensions_supported && swap_chain_adequate
-    }
-
-    fn check_device_extension_support(device: &PhysicalDevice) -> bool {
-        let available_extensions = DeviceExtensions::supported_by_device(*device);
-        let device_extensions = device_extensions();
-        available_extensions.intersection(&device_extensions) == device_extensions
-    }
-
-    fn choose_swap_surface_format(available_formats: &[(Format, ColorSpace)]) -> (Format, ColorSpace) {
-        *available_formats.iter()
-            .find(|(format, color_space)|
-                    *format == Format::B8G8R8A8Unorm && *color_space == ColorSpace::SrgbNonLinear
-                ).unwrap_or_else(|| &available_formats[0])
-
-    }
-
-    fn choose_swap_present_mode(available_present_modes: SupportedPresentModes) -> PresentMode {
-        if available_present_modes.mailbox {
-            PresentMode::Mailbox
-        } else if available_present_modes.immediate {
-                PresentMode::Immediate
-        } else {
-                PresentMode::Fifo
-        }
-    }
-
-    fn choose_swap_extent(capabilities: &Capabilities) -> [u32; 2] {
-        if let Some(current_extent) = capabilities.current_extent {
-            return current_extent
-        } else {
-            let mut actual_extent = [WIDTH, HEIGHT];
-            actual_extent[0] = capabilities.min_image_extent[0]
-                .max(capabilities.max_image_extent[0].min(actual_extent[0]));
-            actual_extent[1] = capabilities.min_image_extent[1]
-                .max(capabilities.max_image_extent[1].min(actual_extent[1]));
-            actual_extent
-        }
+        indices.is_complete() && requirements_supported && swap_chain_adequate
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn create_swap_chain(
         instance: &Arc<Instance>,
         surface: &Arc<Surface<Window>>,
@@ -375,30 +769,36 @@ impl HelloTriangleApplication {
         device: &Arc<Device>,
         graphics_queue: &Arc<Queue>,
         present_queue: &Arc<Queue>,
+        fullscreen_exclusive: FullscreenExclusive,
         old_swapchain: Option<Arc<Swapchain<Window>>>,
     ) -> (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>) {
 
-        let physical_device = PhysicalDevice::from_index(&instance, physical_device_index).unwrap();
+        let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
         let capabilities = surface.capabilities(physical_device)
                             .expect("failed to get surface capabilities");
 
-        let surface_format = Self::choose_swap_surface_format(&capabilities.supported_formats);
-        let present_mode = Self::choose_swap_present_mode(capabilities.present_modes);
-        let extent = Self::choose_swap_extent(&capabilities);
-
-        let mut image_count = capabilities.min_image_count + 1;
-        if capabilities.max_image_count.is_some() && image_count > capabilities.max_image_count.unwrap() {
-            image_count = capabilities.max_image_count.unwrap();
-        }
+        let surface_format = swap_chain::choose_swap_surface_format(&capabilities.supported_formats)
+                            .expect("no supported surface formats!");
+        let present_modes: Vec<_> = capabilities.present_modes.iter().collect();
+        let present_mode = swap_chain::choose_swap_present_mode(&present_modes);
+        let extent = swap_chain::choose_swap_extent(
+                        capabilities.current_extent,
+                        capabilities.min_image_extent,
+                        capabilities.max_image_extent,
+                        window_extent(surface.window())
+                    );
+
+        let image_count = swap_chain::choose_image_count(
+                            capabilities.min_image_count,
+                            capabilities.max_image_count
+                        );
 
         let image_usage = ImageUsage {
             color_attachment: true,
             .. ImageUsage::none()
         };
 
-        let indices = Self::find_queue_families(&surface, &physical_device);
-
-        let sharing: SharingMode = if indices.graphics_family != indices.present_family {
+        let sharing: SharingMode = if graphics_queue.family().id() != present_queue.family().id() {
             vec![graphics_queue, present_queue].as_slice().into()
         } else {
             graphics_queue.into()
@@ -418,7 +818,7 @@ impl HelloTriangleApplication {
                     capabilities.current_transform,
                     CompositeAlpha::Opaque,
                     present_mode,
-                    FullscreenExclusive::Default,
+                    fullscreen_exclusive,
                     true,
                     surface_format.1
                 ).expect("failed to create swap chain!");
@@ -438,7 +838,7 @@ impl HelloTriangleApplication {
                     capabilities.current_transform,
                     CompositeAlpha::Opaque,
                     present_mode,
-                    FullscreenExclusive::Default,
+                    fullscreen_exclusive,
                     true,
                     surface_format.1,
                     old
@@ -455,7 +855,8 @@ impl HelloTriangleApplication {
         device: &Arc<Device>, 
         color_format: Format
     ) -> Arc<dyn RenderPassAbstract + Send + Sync> {
-        Arc::new(single_pass_renderpass!(device.clone(),
+        // The scene is drawn in the first subpass and the GUI and text overlays in the second one.
+        Arc::new(ordered_passes_renderpass!(device.clone(),
             attachments: {
                 color: {
                     load: Clear,
@@ -464,25 +865,72 @@ impl HelloTriangleApplication {
                     samples: 1,
                 }
             },
-            pass: {
-                color: [color],
-                depth_stencil: {}
-            }
+            passes: [
+                {
+                    color: [color],
+                    depth_stencil: {},
+                    input: []
+                },
+                {
+                    color: [color],
+                    depth_stencil: {},
+                    input: []
+                }
+            ]
         ).unwrap())
     }
 
-    fn create_graphics_pipeline(
-        device: &Arc<Device>,
-        swap_chain_extent: [u32; 2],
+    fn scene_pipeline_desc(enabled_features: &Features) -> PipelineDesc {
+        let desc = PipelineDesc {
+            blend_mode: BLEND_MODE,
+            .. PipelineDesc::default()
+        };
+
+        if !WIREFRAME {
+            return desc
+        }
+
+        if !enabled_features.fill_mode_non_solid {
+            println!("Wireframe requested, but fill_mode_non_solid is not supported");
+            return desc
+        }
+
+        PipelineDesc {
+            blend_mode: BLEND_MODE,
+            .. PipelineDesc::wireframe()
+        }
+    }
+
+    fn create_main_pipeline(
+        pipelines: &mut PipelineRegistry,
+        desc: &PipelineDesc,
         render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
-    ) -> Arc<ConcreteGraphicsPipeline> {
-        mod vertex_shader {
-            vulkano_shaders::shader! {
-                ty: "vertex",
-                path: "src/shader_base.vert"
-            }
+        computed_vertices: Option<&mut ComputedVertices>,
+        runtime_shaders: Option<&Rc<RuntimeShaderProgram>>,
+    ) -> PipelineId {
+        if let Some(computed_vertices) = computed_vertices {
+            let id = pipelines.get_or_create("computed", desc, render_pass, SCENE_SUBPASS, ComputedVertices::create_graphics_pipeline);
+            computed_vertices.set_graphics_pipeline(&pipelines.get(id));
+            return id
         }
 
+        if let Some(runtime_shaders) = runtime_shaders {
+            let program = runtime_shaders.clone();
+            return pipelines.get_or_create(SCENE_SHADERS.runtime_name, desc, render_pass, SCENE_SUBPASS, move |device, desc, extent, subpass, cache| {
+                program.create_graphics_pipeline(device, desc, extent, subpass, cache)
+            })
+        }
+
+        pipelines.get_or_create(SCENE_SHADERS.name, desc, render_pass, SCENE_SUBPASS, Self::create_graphics_pipeline)
+    }
+
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult {
         mod fragment_shader {
             vulkano_shaders::shader! {
                 ty: "fragment",
@@ -490,36 +938,36 @@ impl HelloTriangleApplication {
             }
         }
 
-        let vert_shader_module = vertex_shader::Shader::load(device.clone())
-                .expect("failed to create vertex shader module!");
-
         let frag_shader_module = fragment_shader::Shader::load(device.clone())
                 .expect("failed to create fragment shader module!");
 
-        let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
-        let viewport = Viewport {
-            origin: [0.0, 0.0],
-            dimensions,
-            depth_range: 0.0 .. 1.0,
-        };
+        if PALETTE_COLORS {
+            let vert_shader_module = palette_vertex_shader::Shader::load(device.clone())
+                    .expect("failed to create vertex shader module!");
+
+            return desc.build(
+                    device,
+                    BufferlessDefinition {},
+                    vert_shader_module.main_entry_point(),
+                    frag_shader_module.main_entry_point(),
+                    swap_chain_extent,
+                    subpass,
+                    pipeline_cache
+                )
+        }
 
-        Arc::new(GraphicsPipeline::start()
-            .vertex_input(BufferlessDefinition {})
-            .vertex_shader(vert_shader_module.main_entry_point(), ())
-            .triangle_list()
-            .primitive_restart(false)
-            .viewports(vec![viewport])
-            .fragment_shader(frag_shader_module.main_entry_point(), ())
-            .depth_clamp(false)
-            .polygon_mode_fill()
-            .line_width(1.0)
-            .cull_mode_back()
-            .front_face_clockwise()
-            .blend_pass_through()
-            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-            .build(device.clone())
-            .unwrap()
-        )
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+                .expect("failed to create vertex shader module!");
+
+        desc.build(
+                device,
+                BufferlessDefinition {},
+                vert_shader_module.main_entry_point(),
+                frag_shader_module.main_entry_point(),
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
     }
 
     fn create_framebuffers(
@@ -535,18 +983,65 @@ impl HelloTriangleApplication {
             }).collect::<Vec<_>>()
     }
 
-    fn create_command_buffers(&mut self) {
+    fn records_per_frame(&self) -> bool {
+        self.computed_vertices.is_some() || self.particles.is_some() || self.debug_draw.is_some()
+            || self.gui.is_some() || self.text.is_some() || self.camera_controller.is_some()
+            || SCENE_QUERIES
+    }
+
+    // Without a camera controller the scene is drawn as in the tutorial, straight in clip
+    // space.
+    fn scene_uniforms(&self) -> CameraUniforms {
+        match self.camera_controller {
+            Some(_) => self.camera.uniforms(),
+            None => CameraUniforms::clip_space(),
+        }
+    }
+
+    // The camera matrices for the scene pipeline. Every call takes a new buffer from the
+    // pool, so frames still in flight keep the values they were recorded with.
+    fn camera_set(&self, graphics_pipeline: &Arc<ConcreteGraphicsPipeline>) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let uniforms = self.camera_uniforms.next(self.scene_uniforms())
+            .expect("failed to allocate camera uniform buffer");
+        let layout = graphics_pipeline.descriptor_set_layout(0)
+            .expect("scene vertex shader has no camera descriptor set")
+            .clone();
+
+        Arc::new(PersistentDescriptorSet::start(layout)
+            .add_buffer(uniforms).expect("failed to bind camera uniforms")
+            .build().expect("failed to build camera descriptor set")
+        )
+    }
+
+    fn create_all_command_buffers(&mut self) {
+        for window in self.window_list() {
+            self.create_command_buffers(&mut window.borrow_mut());
+        }
+    }
+
+    fn create_command_buffers(&mut self, window: &mut WindowContext) {
+        if self.records_per_frame() {
+            window.command_buffers = vec![];
+            return;
+        }
+
+        self.camera.set_extent(window.swap_chain.dimensions());
+
         let queue_family = self.graphics_queue.family();
-        self.command_buffers = self.swap_chain_framebuffers.iter()
+        let graphics_pipeline = window.pipelines.get(window.main_pipeline);
+        let camera_set = self.camera_set(&graphics_pipeline);
+        window.command_buffers = window.framebuffers.iter()
             .map(|framebuffer| {
                 let vertices = BufferlessVertices { vertices: 3, instances: 1};
 
                 let mut builder = AutoCommandBufferBuilder::primary_simultaneous_use(self.device.clone(), queue_family)
                          .expect("failed to initialise command buffer builder");
-                builder.begin_render_pass(framebuffer.clone(), SubpassContents::Inline, vec![[0.0, 0.0, 0.0, 1.0].into()])
+                builder.begin_render_pass(framebuffer.clone(), SubpassContents::Inline, vec![CLEAR_COLOR.into()])
                          .expect("failed to begin render pass")
-                         .draw(self.graphics_pipeline.clone(), &DynamicState::none(), vertices, (), ())
+                         .draw(graphics_pipeline.clone(), &DynamicState::none(), vertices, camera_set.clone(), ())
                          .expect("failed to draw")
+                         .next_subpass(SubpassContents::Inline)
+                         .expect("failed to start gui subpass")
                          .end_render_pass()
                          .expect("failed to end render pass");
 
@@ -555,27 +1050,225 @@ impl HelloTriangleApplication {
             }).collect()
     }
 
+    fn create_frame_command_buffer(&mut self, window: &WindowContext, image_index: usize) -> FrameCommandBuffer {
+        let primary = window.id() == self.primary_window;
+        if primary {
+            self.build_gui(window.window());
+        }
+        self.camera.set_extent(window.swap_chain.dimensions());
+
+        let framebuffer = window.framebuffers[image_index].clone();
+        if let Some(scene_queries) = window.scene_queries.as_ref() {
+            return self.create_queried_command_buffer(window, scene_queries, framebuffer)
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(self.device.clone(), self.graphics_queue.family())
+                 .expect("failed to initialise command buffer builder");
+
+        self.record_updates(&mut builder, primary);
+        builder.begin_render_pass(framebuffer, SubpassContents::Inline, vec![CLEAR_COLOR.into()])
+                 .expect("failed to begin render pass");
+        self.record_scene(&mut builder, window, primary);
+        builder.next_subpass(SubpassContents::Inline)
+                 .expect("failed to start gui subpass");
+        self.record_overlay(&mut builder, window, primary);
+        builder.end_render_pass()
+                 .expect("failed to end render pass");
+
+        Arc::new(builder.build().expect("failed to build render pass"))
+    }
+
+    // The same frame as secondary command buffers, executed by a primary one that wraps
+    // the scene subpass in the queries. Only the primary window has scene queries.
+    fn create_queried_command_buffer(
+        &mut self,
+        window: &WindowContext,
+        scene_queries: &SceneQueries,
+        framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    ) -> FrameCommandBuffer {
+        let graphics_queue = self.graphics_queue.clone();
+        let queue_family = graphics_queue.family();
+
+        let mut updates = AutoCommandBufferBuilder::secondary_compute_one_time_submit(self.device.clone(), queue_family)
+                 .expect("failed to initialise update command buffer builder");
+        self.record_updates(&mut updates, true);
+
+        let scene_subpass = Subpass::from(window.render_pass.clone(), SCENE_SUBPASS).unwrap();
+        let mut scene = AutoCommandBufferBuilder::secondary_graphics_one_time_submit_inherit_queries(
+                            self.device.clone(),
+                            queue_family,
+                            scene_subpass,
+                            scene_queries.occlusion_query(),
+                            scene_queries.statistics_flags()
+                        ).expect("failed to initialise scene command buffer builder");
+        self.record_scene(&mut scene, window, true);
+
+        let overlay_subpass = Subpass::from(window.render_pass.clone(), OVERLAY_SUBPASS).unwrap();
+        let mut overlay = AutoCommandBufferBuilder::secondary_graphics_one_time_submit(self.device.clone(), queue_family, overlay_subpass)
+                 .expect("failed to initialise overlay command buffer builder");
+        self.record_overlay(&mut overlay, window, true);
+
+        Arc::new(scene_queries.record_frame(
+            framebuffer,
+            CLEAR_COLOR,
+            Arc::new(updates.build().expect("failed to build update command buffer")),
+            Arc::new(scene.build().expect("failed to build scene command buffer")),
+            Arc::new(overlay.build().expect("failed to build overlay command buffer")),
+        ))
+    }
+
+    // Compute work that runs before the render pass.
+    fn record_updates(&mut self, builder: &mut AutoCommandBufferBuilder, primary: bool) {
+        if let Some(particles) = self.particles.as_mut().filter(|_| primary) {
+            particles.record_update(builder);
+        }
+    }
+
+    fn record_scene(&mut self, builder: &mut AutoCommandBufferBuilder, window: &WindowContext, primary: bool) {
+        let computed_vertices = self.computed_vertices.as_ref().filter(|_| primary);
+        let view = self.scene_uniforms().view;
+        let mut render_queue = RenderQueue::new();
+        let scene_center = match computed_vertices {
+            Some(computed_vertices) => computed_vertices.center(),
+            None => TRIANGLE_CENTER,
+        };
+        render_queue.push(BLEND_MODE, view_depth(&view, scene_center), FrameDraw::Scene);
+        if let Some(particles) = self.particles.as_ref().filter(|_| primary) {
+            render_queue.push(particles.blend_mode(), view_depth(&view, particles.center()), FrameDraw::Particles);
+        }
+
+        let graphics_pipeline = window.pipelines.get(window.main_pipeline);
+        for draw in render_queue.drain() {
+            match draw {
+                FrameDraw::Scene => match computed_vertices {
+                    Some(computed_vertices) => computed_vertices.record_draw(builder, &graphics_pipeline),
+                    None => {
+                        let vertices = BufferlessVertices { vertices: 3, instances: 1};
+                        let camera_set = self.camera_set(&graphics_pipeline);
+                        builder.draw(graphics_pipeline.clone(), &DynamicState::none(), vertices, camera_set, ())
+                                 .expect("failed to draw");
+                    }
+                },
+                FrameDraw::Particles => {
+                    if let Some(particles) = &self.particles {
+                        particles.record_draw(builder, &window.pipelines);
+                    }
+                },
+            }
+        }
+
+        if let Some(debug_draw) = self.debug_draw.as_mut().filter(|_| primary) {
+            Self::draw_debug_shapes(debug_draw);
+            if self.camera_controller.is_some() {
+                debug_draw.set_view_projection(self.camera.view_projection());
+            }
+            debug_draw.record_draw(builder, &window.pipelines);
+        }
+    }
+
+    fn record_overlay(&mut self, builder: &mut AutoCommandBufferBuilder, window: &WindowContext, primary: bool) {
+        if let Some(text) = self.text.as_mut().filter(|_| primary) {
+            let label = match self.last_frame_summary {
+                Some(summary) => format!("{:.1} fps", summary.fps),
+                None => String::from("-- fps"),
+            };
+            text.text([8.0, 8.0], &label, [1.0, 1.0, 1.0, 1.0]);
+            text.record_draw(builder, &window.pipelines);
+        }
+        if let Some(gui) = self.gui.as_mut().filter(|_| primary) {
+            gui.record_draw(builder, &window.pipelines);
+        }
+    }
+
+    fn build_gui(&mut self, window: &Window) {
+        let gui = match self.gui.as_mut() {
+            Some(gui) => gui,
+            None => return,
+        };
+
+        let frame_pacer = &mut self.frame_pacer;
+        let target_fps = &mut self.gui_target_fps;
+        let summary = self.last_frame_summary;
+        let scene_counts = self.last_scene_counts;
+
+        gui.run(window, &mut self.uploader, |ctx| {
+            egui::Window::new("Stats").show(ctx, |ui| {
+                match summary {
+                    Some(summary) => {
+                        ui.label(format!("{:.1} fps", summary.fps));
+                        ui.label(format!("cpu {:.2} ms", summary.average.cpu.as_secs_f64() * 1000.0));
+                        ui.label(format!("p95 {:.2} ms", summary.p95.as_secs_f64() * 1000.0));
+                        if let Some(gpu) = summary.average.gpu {
+                            ui.label(format!("gpu {:.2} ms", gpu.as_secs_f64() * 1000.0));
+                        }
+                    },
+                    None => {
+                        ui.label("collecting frame times");
+                    },
+                }
+                if let Some(scene_counts) = scene_counts {
+                    ui.label(format!("{} samples", scene_counts.samples));
+                    if let Some(pipeline) = scene_counts.pipeline {
+                        ui.label(format!("{} vertices, {} primitives", pipeline.vertices, pipeline.primitives));
+                        ui.label(format!("{} vs / {} fs invocations", pipeline.vertex_invocations, pipeline.fragment_invocations));
+                    }
+                }
+
+                if ui.add(egui::Slider::new(target_fps, 10..=240).text("target fps")).changed() {
+                    frame_pacer.set_target_fps(Some(*target_fps));
+                }
+            });
+        });
+    }
+
+    fn draw_debug_shapes(debug_draw: &mut DebugDraw) {
+        debug_draw.aabb([-0.5, -0.5, 0.0], [0.5, 0.5, 0.0], [1.0, 1.0, 0.0, 1.0]);
+        debug_draw.axis([0.0, 0.0, 0.0], 0.25);
+        debug_draw.sphere(TRIANGLE_CENTER, 0.35, [0.0, 1.0, 1.0, 1.0]);
+        debug_draw.grid([0.0, -0.5, 0.0], 4.0, 8, [0.5, 0.5, 0.5, 1.0]);
+    }
+
     fn create_sync_objects(device: &Arc<Device>) -> Box<dyn GpuFuture> {
         Box::new(sync::now(device.clone())) as Box<dyn GpuFuture>
     }
 
     fn find_queue_families(surface: &Arc<Surface<Window>>, device: &PhysicalDevice) -> QueueFamilyIndices {
         let mut indices = QueueFamilyIndices::new();
+        let mut graphics_and_present_family = None;
+
+        for queue_family in device.queue_families() {
+            let id = queue_family.id();
+            let supports_graphics = queue_family.supports_graphics();
+            let supports_compute = queue_family.supports_compute();
+            let supports_present = surface.is_supported(queue_family).unwrap();
+
+            if supports_graphics && supports_present && graphics_and_present_family.is_none() {
+                graphics_and_present_family = Some(id);
+            }
+
+            if supports_graphics && indices.graphics_family.is_none() {
+                indices.graphics_family = Some(id);
+            }
 
-        for (i, queue_family) in device.queue_families().enumerate() {
-            if queue_family.supports_graphics() {
-                indices.graphics_family = i as i32;
+            if supports_present && indices.present_family.is_none() {
+                indices.present_family = Some(id);
             }
 
-            if surface.is_supported(queue_family).unwrap() {
-                indices.present_family = i as i32;
+            if queue_family.explicitly_supports_transfers() && !supports_graphics && !supports_compute
+                && indices.transfer_family.is_none() {
+                indices.transfer_family = Some(id);
             }
 
-            if indices.is_complete() {
-                break;
+            if supports_compute && !supports_graphics && indices.compute_family.is_none() {
+                indices.compute_family = Some(id);
             }
         }
 
+        if graphics_and_present_family.is_some() {
+            indices.graphics_family = graphics_and_present_family;
+            indices.present_family = graphics_and_present_family;
+        }
+
         indices
     }
 
@@ -583,111 +1276,433 @@ impl HelloTriangleApplication {
         instance: &Arc<Instance>,
         surface: &Arc<Surface<Window>>,
         physical_device_index: usize,
-    ) -> (Arc<Device>, Arc<Queue>, Arc<Queue>) {
+    ) -> (Arc<Device>, DeviceQueues) {
 
-        let physical_device = PhysicalDevice::from_index(&instance, physical_device_index).unwrap();
-        let indices = Self::find_queue_families(&surface, &physical_device);
-
-        let families = [indices.graphics_family, indices.present_family];
-        use std::iter::FromIterator;
-        let unique_queue_families: HashSet<&i32> = HashSet::from_iter(families.iter());
+        let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
+        let indices = Self::find_queue_families(surface, &physical_device);
 
         let queue_priority = 1.0;
-        let queue_families = unique_queue_families.iter().map(|i| {
-            (physical_device.queue_families().nth(**i as usize).unwrap(), queue_priority)
+        let queue_families = indices.unique_families().into_iter().map(|id| {
+            (physical_device.queue_family_by_id(id).unwrap(), queue_priority)
         });
 
-        let (device, mut queues) = Device::new(
+        let requirements = device_requirements(&instance.loaded_extensions());
+        let enabled_features = requirements.enabled_features(&physical_device);
+        let enabled_extensions = requirements.enabled_extensions(&physical_device);
+
+        let (device, queues) = Device::new(
                                         physical_device, 
-                                        &Features::none(),
-                                        &device_extensions(),
+                                        &enabled_features,
+                                        &enabled_extensions,
                                         queue_families    
                                     ).expect("failed to create logical device!");
 
-        let graphics_queue = queues.next().unwrap();
-        let present_queue = queues.next().unwrap_or_else(|| graphics_queue.clone());
+        let queues: HashMap<u32, Arc<Queue>> = queues
+            .map(|queue| (queue.family().id(), queue))
+            .collect();
+        let queue_for = |family: Option<u32>| family.map(|id| queues[&id].clone());
+
+        let graphics = queue_for(indices.graphics_family).unwrap();
+        let transfer = queue_for(indices.transfer_family).unwrap_or_else(|| graphics.clone());
+        let compute = queue_for(indices.compute_family).unwrap_or_else(|| graphics.clone());
 
-        (device, graphics_queue, present_queue)
+        let mut all: Vec<_> = queues.into_values().collect();
+        all.sort_by_key(|queue| queue.family().id());
+
+        (device, DeviceQueues { graphics, transfer, compute, all })
 
     }
 
     fn draw_frame(&mut self) {
+        let frame_start = Instant::now();
+        let mut timing = FrameTiming::default();
+        let mut drawn = false;
+
+        for window in self.window_list() {
+            let mut window = window.borrow_mut();
+            if window.minimized {
+                continue;
+            }
+
+            if let Some(window_timing) = self.draw_window(&mut window) {
+                timing.acquire += window_timing.acquire;
+                timing.submit += window_timing.submit;
+                timing.gpu = add_gpu(timing.gpu, window_timing.gpu);
+                drawn = true;
+            }
+        }
+
+        if drawn {
+            timing.cpu = frame_start.elapsed();
+            self.frame_stats.record(timing);
+        }
+    }
+
+    // Returns the time spent acquiring the swap chain image and submitting the frame, and
+    // the GPU time of an earlier frame of the window, or `None` when nothing was drawn.
+    fn draw_window(&mut self, window: &mut WindowContext) -> Option<FrameTiming> {
         // let (image_index, _suboptimal, acquire_future) = acquire_next_image(self.swap_chain.clone(), None).unwrap();
 
-        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+        window.previous_frame_end.as_mut().unwrap().cleanup_finished();
 
-        if self.recreate_swap_chain {
-            self.recreate_swap_chain();
-            self.recreate_swap_chain = false;
+        if window.recreate_swap_chain {
+            if !self.recreate_swap_chain(window) {
+                return None;
+            }
+            window.recreate_swap_chain = false;
         }
 
+        let acquire_start = Instant::now();
         let (image_index, suboptimal, acquire_future) = 
-            match acquire_next_image(self.swap_chain.clone(), None) {
+            match acquire_next_image(window.swap_chain.clone(), None) {
                 Ok(r) => r,
                 Err(AcquireError::OutOfDate) => {
-                    self.recreate_swap_chain = true;
-                    return;
+                    window.recreate_swap_chain = true;
+                    return None;
                 },
                 Err(err) => panic!("failed to acquire next image {:?}", err)
             };
 
+        let acquire_time = acquire_start.elapsed();
+
         if suboptimal {
-            self.recreate_swap_chain = true;
+            window.recreate_swap_chain = true;
         }
 
-        let command_buffer = self.command_buffers[image_index].clone();
+        let command_buffer: FrameCommandBuffer = if self.records_per_frame() {
+            self.create_frame_command_buffer(window, image_index)
+        } else {
+            window.command_buffers[image_index].clone()
+        };
 
-        let future = self.previous_frame_end.take().unwrap()
-            .join(acquire_future)
-            .then_execute(self.graphics_queue.clone(), command_buffer)
-            .unwrap()
-            .then_swapchain_present(self.present_queue.clone(), self.swap_chain.clone(), image_index)
+        // Uploaded resources and computed vertices are only used by the passes drawn into
+        // the primary window. The vertices are computed once the last frame that drew them
+        // is done.
+        let mut previous_frame_end = window.previous_frame_end.take().unwrap();
+        if window.id() == self.primary_window {
+            self.uploader.flush();
+            if let Some(upload_future) = self.uploader.take_future() {
+                previous_frame_end = Box::new(previous_frame_end.join(upload_future));
+            }
+            if let Some(computed_vertices) = self.computed_vertices.as_ref() {
+                previous_frame_end = computed_vertices.dispatch(previous_frame_end);
+            }
+        }
+
+        let gpu_time = window.gpu_timer.as_mut().and_then(|timer| timer.resolve());
+        if let Some(scene_counts) = window.scene_queries.as_mut().and_then(|queries| queries.resolve()) {
+            self.last_scene_counts = Some(scene_counts);
+        }
+
+        let submit_start = Instant::now();
+        let queue = &self.graphics_queue;
+        let mut future = previous_frame_end.join(acquire_future).boxed();
+        if let Some(timer) = window.gpu_timer.as_ref() {
+            future = future.then_execute(queue.clone(), timer.begin()).unwrap().boxed();
+        }
+        future = future.then_execute(queue.clone(), command_buffer).unwrap().boxed();
+        if let Some(timer) = window.gpu_timer.as_ref() {
+            future = future.then_execute(queue.clone(), timer.end()).unwrap().boxed();
+        }
+        let future = future
+            .then_swapchain_present(window.present_queue.clone(), window.swap_chain.clone(), image_index)
+            .boxed()
             .then_signal_fence_and_flush();
+        let submit_time = submit_start.elapsed();
 
-        match future {
-            Ok(future) => {
-                self.previous_frame_end = Some(Box::new(future) as Box<_>);
-            }
+        // Vulkano only implements `GpuFuture` for a shared fence future behind an `Arc`,
+        // and the frame's futures never leave this thread.
+        #[allow(clippy::arc_with_non_send_sync)]
+        let fence: Option<FrameFence> = match future {
+            Ok(future) => Some(Arc::new(future)),
             Err(vulkano::sync::FlushError::OutOfDate) => {
-                self.recreate_swap_chain = true;
-                self.previous_frame_end = Some(Box::new(vulkano::sync::now(self.device.clone())) as Box<_>);
+                window.recreate_swap_chain = true;
+                None
             },
             Err(e) => {
                 println!("{:?}",e);
-                self.previous_frame_end = Some(Box::new(vulkano::sync::now(self.device.clone())) as Box<_>);
+                None
+            }
+        };
+        window.previous_frame_end = Some(match &fence {
+            Some(fence) => Box::new(fence.clone()) as Box<_>,
+            None => Box::new(vulkano::sync::now(self.device.clone())) as Box<_>,
+        });
+        if let Some(scene_queries) = window.scene_queries.as_mut() {
+            scene_queries.submitted(fence.clone());
+        }
+        if let Some(timer) = window.gpu_timer.as_mut() {
+            timer.submitted(fence);
+        }
+
+        Some(FrameTiming {
+            acquire: acquire_time,
+            submit: submit_time,
+            gpu: gpu_time,
+            .. FrameTiming::default()
+        })
+    }
+
+    fn report_frame_stats(&mut self, now: Instant) {
+        if let Some(summary) = self.frame_stats.report(now) {
+            self.last_frame_summary = Some(summary);
+            let report = match self.last_scene_counts {
+                Some(scene_counts) => format!("{} | {}", summary, scene_counts),
+                None => summary.to_string(),
+            };
+            if FRAME_STATS_IN_TITLE {
+                self.windows[&self.primary_window].borrow().window().set_title(&format!("{} - {}", WINDOW_TITLE, report));
+            } else {
+                println!("{}", report);
             }
         }
     }
 
-    fn recreate_swap_chain(&mut self) {
+    fn recreate_swap_chain(&mut self, window: &mut WindowContext) -> bool {
+        let physical_device = PhysicalDevice::from_index(&self.instance, self.physical_device_index).unwrap();
+        let capabilities = window.surface.capabilities(physical_device)
+                            .expect("failed to get surface capabilities");
+        let extent = swap_chain::choose_swap_extent(
+                        capabilities.current_extent,
+                        capabilities.min_image_extent,
+                        capabilities.max_image_extent,
+                        window.extent()
+                    );
+
+        if !swap_chain::is_drawable_extent(extent) {
+            return false;
+        }
+
         let (new_swap_chain, new_images) = Self::create_swap_chain(
                                         &self.instance, 
-                                        &self.surface,
+                                        &window.surface,
                                         self.physical_device_index,
                                         &self.device,
                                         &self.graphics_queue,
-                                        &self.present_queue,
-                                        Some(self.swap_chain.clone())
+                                        &window.present_queue,
+                                        window.window_mode.fullscreen_exclusive(self.enabled_extensions()),
+                                        Some(window.swap_chain.clone())
                                     );
 
-        self.swap_chain = new_swap_chain;
-        self.swap_chain_images = new_images;
+        window.swap_chain = new_swap_chain;
+        window.swap_chain_images = new_images;
+
+        let render_pass = Self::create_render_pass(&self.device, window.swap_chain.format());
+        window.pipelines.rebuild(&window.render_pass, &render_pass, window.swap_chain.dimensions())
+            .expect("failed to rebuild pipelines for the new swap chain");
+        window.render_pass = render_pass;
+
+        window.framebuffers = Self::create_framebuffers(&window.swap_chain_images, &window.render_pass);
+        self.create_command_buffers(window);
+
+        true
+    }
 
-        self.render_pass = Self::create_render_pass(&self.device, self.swap_chain.format());
-        self.graphics_pipeline = Self::create_graphics_pipeline(&self.device, self.swap_chain.dimensions(),&self.render_pass);
+    fn enabled_features(&self) -> &Features {
+        self.device.enabled_features()
+    }
 
-        self.swap_chain_framebuffers = Self::create_framebuffers(&self.swap_chain_images, &self.render_pass);
-        self.create_command_buffers();
+    fn enabled_extensions(&self) -> &DeviceExtensions {
+        self.device.loaded_extensions()
+    }
+
+    fn reload_shaders(&mut self) {
+        let changed = self.shader_watcher.as_ref().is_some_and(|watcher| watcher.changed());
+        if !changed {
+            return;
+        }
+
+        let reloaded = RuntimeShaderProgram::load(
+                            &self.device,
+                            Path::new(VERTEX_SHADER_PATH),
+                            Path::new(FRAGMENT_SHADER_PATH),
+                            SCENE_SHADERS.defines
+                        )
+            .and_then(|program| {
+                let program = Rc::new(program);
+                // Every window's pipeline is built before any is swapped in, so a failure
+                // leaves all of them on the last good shaders.
+                let mut replacements = Vec::new();
+                for window in self.windows.values() {
+                    let window = window.borrow();
+                    // The primary window draws the computed vertices instead when enabled.
+                    if window.id() == self.primary_window && self.computed_vertices.is_some() {
+                        continue;
+                    }
+
+                    let shaders = program.clone();
+                    let replacement = window.pipelines.build_replacement(window.main_pipeline, move |device, desc, extent, subpass, cache| {
+                        shaders.create_graphics_pipeline(device, desc, extent, subpass, cache)
+                    }).map_err(ShaderError::Pipeline)?;
+                    replacements.push((window.id(), replacement));
+                }
+
+                for (window_id, replacement) in replacements {
+                    self.windows[&window_id].borrow_mut().pipelines.replace(replacement);
+                }
+                Ok(program)
+            });
+
+        match reloaded {
+            Ok(program) => {
+                println!("Shaders reloaded");
+                if let Some(watcher) = self.shader_watcher.as_mut() {
+                    if let Err(err) = watcher.watch(&program.dependencies()) {
+                        println!("failed to watch shader includes: {:?}", err);
+                    }
+                }
+                self.runtime_shaders = Some(program);
+                self.create_all_command_buffers();
+                self.frame_pacer.request_redraw();
+            },
+            Err(err) => println!("{}, keeping the last good pipeline", err),
+        }
+    }
+
+    // Applies the input gathered since the last call. Returns false when the application
+    // should quit.
+    fn process_input(&mut self, now: Instant, window_target: &EventLoopWindowTarget<()>) -> bool {
+        if self.input.was_action_triggered(Action::Quit) {
+            return false
+        }
+
+        if self.input.was_action_triggered(Action::NewWindow) {
+            self.open_window(window_target);
+            self.frame_pacer.request_redraw();
+        }
+
+        if self.input.was_action_triggered(Action::ToggleFullscreen) {
+            self.toggle_fullscreen();
+        }
+
+        let elapsed = now - self.last_camera_update;
+        self.last_camera_update = now;
+        if let Some(controller) = self.camera_controller.as_mut() {
+            if controller.update(&mut self.camera, &self.input, elapsed) {
+                self.frame_pacer.request_redraw();
+            }
+        }
+
+        self.input.end_frame();
+        true
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let mut window = match self.windows.get(&self.focused_window) {
+            Some(window) => window.borrow_mut(),
+            None => return,
+        };
+
+        let window_mode = if window.window_mode.is_fullscreen() {
+            WindowMode::Windowed
+        } else {
+            FULLSCREEN_MODE
+        };
+        window.set_window_mode(window_mode);
+        self.frame_pacer.request_redraw();
+    }
+
+    // Returns true when every window is minimized and there is nothing to draw.
+    fn update_minimized(&mut self) -> bool {
+        let mut all_minimized = true;
+        for window in self.windows.values() {
+            let mut window = window.borrow_mut();
+            let minimized = window.is_minimized();
+            if window.minimized && !minimized {
+                window.recreate_swap_chain = true;
+                self.frame_pacer.request_redraw();
+            }
+            window.minimized = minimized;
+            all_minimized &= minimized;
+        }
+
+        all_minimized
+    }
+
+    fn request_recreate_swap_chain(&mut self, window_id: WindowId) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.borrow_mut().recreate_swap_chain = true;
+        }
+        self.frame_pacer.request_redraw();
     }
 
     fn main_loop(mut self, events_loop: EventLoop<()>) {
-        events_loop.run(move |event, _, control_flow| {
+        events_loop.run(move |event, window_target, control_flow| {
+            if let Event::WindowEvent { event, window_id } = &event {
+                // The GUI is only drawn into the primary window.
+                let primary = *window_id == self.primary_window;
+                let gui_wants_event = match self.gui.as_mut().filter(|_| primary) {
+                    Some(gui) => gui.handle_event(event),
+                    None => false,
+                };
+
+                // Releases always reach the input state, otherwise a key held when the
+                // GUI took the keyboard would stay pressed.
+                let released = matches!(event,
+                    WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Released, .. }, .. }
+                    | WindowEvent::MouseInput { state: ElementState::Released, .. });
+
+                if gui_wants_event {
+                    self.frame_pacer.request_redraw();
+                }
+                if !gui_wants_event || released {
+                    self.input.handle_event(event);
+                }
+            }
+
             match event {
-                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
-                    *control_flow = ControlFlow::Exit;
+                Event::WindowEvent { event: WindowEvent::CloseRequested, window_id } => {
+                    if window_id == self.primary_window {
+                        *control_flow = ControlFlow::Exit;
+                    } else {
+                        self.windows.remove(&window_id);
+                    }
+                },
+                Event::WindowEvent { event: WindowEvent::Resized(_), window_id } => {
+                    self.request_recreate_swap_chain(window_id);
+                },
+                Event::WindowEvent { event: WindowEvent::ScaleFactorChanged { .. }, window_id } => {
+                    self.request_recreate_swap_chain(window_id);
+                },
+                Event::WindowEvent { event: WindowEvent::Focused(true), window_id } => {
+                    self.focused_window = window_id;
                 },
                 Event::RedrawEventsCleared => {
-                    self.draw_frame();
+                    if !self.process_input(Instant::now(), window_target) {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+
+                    if self.update_minimized() {
+                        *control_flow = ControlFlow::Wait;
+                        return;
+                    }
+
+                    // The shader watcher wakes the loop with a user event when a file
+                    // changes, so this also runs while waiting for input.
+                    self.reload_shaders();
+
+                    let now = Instant::now();
+                    if self.frame_pacer.should_render(now) {
+                        self.draw_frame();
+                        self.frame_pacer.frame_rendered(now);
+
+                        if self.windows.values().any(|window| window.borrow().recreate_swap_chain) {
+                            self.frame_pacer.request_redraw();
+                        }
+                        if self.gui.as_ref().is_some_and(|gui| gui.needs_repaint()) {
+                            self.frame_pacer.request_redraw();
+                        }
+                    }
+
+                    self.report_frame_stats(now);
+
+                    *control_flow = self.frame_pacer.control_flow();
+                },
+                Event::LoopDestroyed => {
+                    if let Err(err) = self.pipeline_cache.save() {
+                        println!("failed to save pipeline cache: {:?}", err);
+                    }
                 },
                 _ => ()
             }
@@ -0,0 +1,199 @@
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{
+        vertex::BufferlessDefinition,
+        vertex::BufferlessVertices,
+        cache::PipelineCache,
+    };
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::compute::ComputePass;
+use crate::pipeline_desc::{BlendMode, CullMode, PipelineDesc};
+use crate::pipeline_registry::{PipelineId, PipelineRegistry, PipelineResult};
+use crate::uploader::Uploader;
+
+const LOCAL_SIZE_X: u32 = 256;
+const VERTICES_PER_PARTICLE: usize = 6;
+const MAX_DELTA_TIME: f32 = 0.1;
+const BLEND_MODE: BlendMode = BlendMode::Additive;
+
+mod compute_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shader_particles.comp",
+        include: ["src"]
+    }
+}
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shader_particles.vert",
+        include: ["src"]
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shader_particles.frag"
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+}
+
+// Particles live in a single storage buffer. The initial state is uploaded on the
+// transfer queue and the first frame waits on the upload future; afterwards the update
+// dispatch and the draw are recorded in the same graphics command buffer, so vulkano
+// places the barrier between the compute writes and the vertex shader reads.
+pub struct ParticleSystem {
+    count: u32,
+    update: ComputePass,
+    particles: Arc<DeviceLocalBuffer<[Particle]>>,
+    update_set: Arc<dyn DescriptorSet + Send + Sync>,
+
+    pipeline: Option<PipelineId>,
+    draw_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+
+    last_update: Instant,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &Arc<Device>,
+        uploader: &mut Uploader,
+        pipeline_cache: &Arc<PipelineCache>,
+        count: u32,
+    ) -> Self {
+        let shader = compute_shader::Shader::load(device.clone())
+                .expect("failed to create particle compute shader module!");
+        let update = ComputePass::new(device, &shader.main_entry_point(), pipeline_cache);
+
+        let usage = BufferUsage {
+            storage_buffer: true,
+            .. BufferUsage::none()
+        };
+        let particles = uploader.upload_buffer(Self::initial_particles(count), usage);
+        let update_set = update.buffer_set(0, particles.clone());
+
+        Self {
+            count,
+            update,
+            particles,
+            update_set,
+
+            pipeline: None,
+            draw_set: None,
+
+            last_update: Instant::now(),
+        }
+    }
+
+    fn initial_particles(count: u32) -> impl ExactSizeIterator<Item = Particle> {
+        let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+        (0..count).map(move |i| {
+            let angle = i as f32 * golden_angle;
+            let radius = 0.9 * (i as f32 / count as f32).sqrt();
+            let (sin, cos) = angle.sin_cos();
+            Particle {
+                position: [radius * cos, radius * sin],
+                velocity: [-0.5 * radius * sin, 0.5 * radius * cos],
+            }
+        })
+    }
+
+    // Registers the draw pipeline in the primary window's registry, which rebuilds it
+    // with the swap chain. The descriptor set only has to stay compatible with the
+    // pipeline layout, so it outlives those rebuilds.
+    pub fn register_pipeline(
+        &mut self,
+        pipelines: &mut PipelineRegistry,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        subpass: u32,
+    ) {
+        let desc = PipelineDesc {
+            cull_mode: CullMode::None,
+            blend_mode: BLEND_MODE,
+            .. PipelineDesc::default()
+        };
+        let id = pipelines.get_or_create("particles", &desc, render_pass, subpass, Self::create_graphics_pipeline);
+
+        let layout = pipelines.get(id).descriptor_set_layout(0)
+            .expect("particle vertex shader has no descriptor set")
+            .clone();
+        self.draw_set = Some(Arc::new(PersistentDescriptorSet::start(layout)
+            .add_buffer(self.particles.clone()).expect("failed to bind particles")
+            .build().expect("failed to build particle descriptor set")
+        ));
+        self.pipeline = Some(id);
+    }
+
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+                .expect("failed to create particle vertex shader module!");
+
+        let frag_shader_module = fragment_shader::Shader::load(device.clone())
+                .expect("failed to create particle fragment shader module!");
+
+        desc.build(
+                device,
+                BufferlessDefinition {},
+                vert_shader_module.main_entry_point(),
+                frag_shader_module.main_entry_point(),
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
+    }
+
+    pub fn record_update(&mut self, builder: &mut AutoCommandBufferBuilder) {
+        let now = Instant::now();
+        let delta_time = now.duration_since(self.last_update).as_secs_f32().min(MAX_DELTA_TIME);
+        self.last_update = now;
+
+        let push_constants = compute_shader::ty::PushConstants {
+            delta_time,
+            particle_count: self.count,
+        };
+        let group_count = self.count.div_ceil(LOCAL_SIZE_X);
+
+        self.update.record(builder, [group_count, 1, 1], self.update_set.clone(), push_constants);
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        BLEND_MODE
+    }
+
+    // The update pulls every particle towards the origin, where the swirl is centred.
+    pub fn center(&self) -> [f32; 3] {
+        [0.0, 0.0, 0.0]
+    }
+
+    pub fn record_draw(&self, builder: &mut AutoCommandBufferBuilder, pipelines: &PipelineRegistry) {
+        let pipeline = pipelines.get(self.pipeline.expect("particle pipeline not registered"));
+        let draw_set = self.draw_set.clone()
+            .expect("particle pipeline not registered");
+        let vertices = BufferlessVertices { vertices: VERTICES_PER_PARTICLE, instances: self.count as usize };
+
+        builder.draw(pipeline, &DynamicState::none(), vertices, draw_set, ())
+            .expect("failed to draw particles");
+    }
+}
@@ -0,0 +1,158 @@
+use vulkano::device::Device;
+use vulkano::instance::PhysicalDevice;
+use vulkano::pipeline::cache::PipelineCache;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const CACHE_FILE: &str = "pipeline_cache.bin";
+const MAGIC: &[u8; 4] = b"VKPC";
+const HEADER_LEN: usize = 4 + 16 + 4;
+
+// Identifies the device and driver that produced the cache data. A cache written by a
+// different GPU or driver version is discarded instead of being handed to the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CacheKey {
+    uuid: [u8; 16],
+    driver_version: u32,
+}
+
+impl CacheKey {
+    fn new(physical_device: &PhysicalDevice) -> Self {
+        Self {
+            uuid: *physical_device.uuid(),
+            driver_version: physical_device.driver_version(),
+        }
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&self.uuid);
+        header.extend_from_slice(&self.driver_version.to_le_bytes());
+        header
+    }
+
+    fn with_header(&self, data: &[u8]) -> Vec<u8> {
+        let mut bytes = self.header();
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn strip_header<'a>(&self, bytes: &'a [u8]) -> Option<&'a [u8]> {
+        if bytes.len() < HEADER_LEN || bytes[..HEADER_LEN] != self.header()[..] {
+            return None
+        }
+
+        Some(&bytes[HEADER_LEN..])
+    }
+}
+
+pub struct PipelineCacheFile {
+    cache: Arc<PipelineCache>,
+    key: CacheKey,
+    path: Option<PathBuf>,
+}
+
+impl PipelineCacheFile {
+    pub fn load(device: &Arc<Device>) -> Self {
+        let key = CacheKey::new(&device.physical_device());
+        let path = dirs::cache_dir()
+            .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(CACHE_FILE));
+
+        let data = path.as_ref().and_then(|path| fs::read(path).ok());
+        let cache = match data.as_ref().and_then(|bytes| key.strip_header(bytes)) {
+            Some(data) => {
+                // The driver validates the data against its own header as well, and an
+                // unusable cache only costs the time to rebuild the pipelines.
+                unsafe { PipelineCache::with_data(device.clone(), data) }
+                    .unwrap_or_else(|_| Self::empty(device))
+            },
+            None => {
+                if data.is_some() {
+                    println!("Discarding pipeline cache from a different device or driver");
+                }
+                Self::empty(device)
+            },
+        };
+
+        Self { cache, key, path }
+    }
+
+    fn empty(device: &Arc<Device>) -> Arc<PipelineCache> {
+        PipelineCache::empty(device.clone())
+            .expect("failed to create pipeline cache!")
+    }
+
+    pub fn cache(&self) -> &Arc<PipelineCache> {
+        &self.cache
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let data = self.cache.get_data()
+            .map_err(|err| io::Error::other(format!("{:?}", err)))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, self.key.with_header(&data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: CacheKey = CacheKey {
+        uuid: [7; 16],
+        driver_version: 42,
+    };
+
+    #[test]
+    fn header_round_trips() {
+        let bytes = KEY.with_header(b"driver data");
+
+        assert_eq!(bytes.len(), HEADER_LEN + 11);
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(KEY.strip_header(&bytes), Some(&b"driver data"[..]));
+    }
+
+    #[test]
+    fn empty_data_round_trips() {
+        assert_eq!(KEY.strip_header(&KEY.with_header(&[])), Some(&[][..]));
+    }
+
+    #[test]
+    fn other_device_is_rejected() {
+        let other = CacheKey { uuid: [8; 16], ..KEY };
+        assert_eq!(KEY.strip_header(&other.with_header(b"data")), None);
+    }
+
+    #[test]
+    fn other_driver_version_is_rejected() {
+        let other = CacheKey { driver_version: 43, ..KEY };
+        assert_eq!(KEY.strip_header(&other.with_header(b"data")), None);
+    }
+
+    #[test]
+    fn missing_magic_is_rejected() {
+        let mut bytes = KEY.with_header(b"data");
+        bytes[0] = b'X';
+        assert_eq!(KEY.strip_header(&bytes), None);
+    }
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        let bytes = KEY.with_header(&[]);
+        assert_eq!(KEY.strip_header(&bytes[..HEADER_LEN - 1]), None);
+        assert_eq!(KEY.strip_header(&[]), None);
+    }
+}
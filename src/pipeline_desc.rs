@@ -0,0 +1,251 @@
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{
+        GraphicsPipeline,
+        blend::{AttachmentBlend, BlendFactor, BlendOp},
+        cache::PipelineCache,
+        depth_stencil::DepthStencil,
+        shader::GraphicsEntryPointAbstract,
+        vertex::VertexDefinition,
+        viewport::Viewport,
+    };
+
+use std::sync::Arc;
+
+use crate::pipeline_registry::PipelineResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(unused)]
+pub enum Topology {
+    PointList,
+    LineList,
+    LineStrip,
+    TriangleList,
+    TriangleStrip,
+}
+
+// Anything but `Fill` needs the `fill_mode_non_solid` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(unused)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(unused)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(unused)]
+pub enum FrontFace {
+    Clockwise,
+    CounterClockwise,
+}
+
+// `Alpha` expects straight colors from the fragment shader, `PremultipliedAlpha`
+// colors already multiplied by their alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Opaque,
+    Additive,
+    Alpha,
+    PremultipliedAlpha,
+}
+
+impl BlendMode {
+    pub fn is_transparent(self) -> bool {
+        self != BlendMode::Opaque
+    }
+
+    fn attachment_blend(self) -> AttachmentBlend {
+        match self {
+            BlendMode::Opaque => AttachmentBlend::pass_through(),
+            BlendMode::Additive => AttachmentBlend {
+                enabled: true,
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::SrcAlpha,
+                color_destination: BlendFactor::One,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::One,
+                alpha_destination: BlendFactor::One,
+                .. AttachmentBlend::pass_through()
+            },
+            BlendMode::Alpha => AttachmentBlend {
+                enabled: true,
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::SrcAlpha,
+                color_destination: BlendFactor::OneMinusSrcAlpha,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::One,
+                alpha_destination: BlendFactor::OneMinusSrcAlpha,
+                .. AttachmentBlend::pass_through()
+            },
+            BlendMode::PremultipliedAlpha => AttachmentBlend {
+                enabled: true,
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::One,
+                color_destination: BlendFactor::OneMinusSrcAlpha,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::One,
+                alpha_destination: BlendFactor::OneMinusSrcAlpha,
+                .. AttachmentBlend::pass_through()
+            },
+        }
+    }
+}
+
+// Depth testing needs a subpass with a depth attachment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(unused)]
+pub enum DepthState {
+    Disabled,
+    TestOnly,
+    TestAndWrite,
+}
+
+impl DepthState {
+    fn depth_stencil(self) -> DepthStencil {
+        match self {
+            DepthState::Disabled => DepthStencil::disabled(),
+            DepthState::TestOnly => DepthStencil {
+                depth_write: false,
+                .. DepthStencil::simple_depth_test()
+            },
+            DepthState::TestAndWrite => DepthStencil::simple_depth_test(),
+        }
+    }
+}
+
+// Fixed-function state of a graphics pipeline. The default matches the state the
+// tutorial pipeline always used: filled triangles, back-face culling, clockwise front
+// faces and no blending.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipelineDesc {
+    pub topology: Topology,
+    pub polygon_mode: PolygonMode,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub line_width: f32,
+    pub blend_mode: BlendMode,
+    pub depth_state: DepthState,
+    // Scissor rectangles are set per draw through `DynamicState::scissors`.
+    pub dynamic_scissor: bool,
+}
+
+impl Default for PipelineDesc {
+    fn default() -> Self {
+        Self {
+            topology: Topology::TriangleList,
+            polygon_mode: PolygonMode::Fill,
+            cull_mode: CullMode::Back,
+            front_face: FrontFace::Clockwise,
+            line_width: 1.0,
+            blend_mode: BlendMode::Opaque,
+            depth_state: DepthState::Disabled,
+            dynamic_scissor: false,
+        }
+    }
+}
+
+impl PipelineDesc {
+    pub fn wireframe() -> Self {
+        Self {
+            polygon_mode: PolygonMode::Line,
+            cull_mode: CullMode::None,
+            .. Self::default()
+        }
+    }
+
+    pub fn lines() -> Self {
+        Self {
+            topology: Topology::LineList,
+            cull_mode: CullMode::None,
+            .. Self::default()
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn build<Vdef, Vs, Fs>(
+        &self,
+        device: &Arc<Device>,
+        vertex_input: Vdef,
+        vertex_shader: Vs,
+        fragment_shader: Fs,
+        extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult<Vdef>
+    where
+        Vdef: VertexDefinition<Vs::InputDefinition>,
+        Vs: GraphicsEntryPointAbstract<SpecializationConstants = ()>,
+        Fs: GraphicsEntryPointAbstract<SpecializationConstants = ()>,
+        Vs::PipelineLayout: Clone + Send + Sync + 'static,
+        Fs::PipelineLayout: Clone + Send + Sync + 'static,
+    {
+        // Without the `wide_lines` feature the only valid width is 1.
+        let line_width = if device.enabled_features().wide_lines { self.line_width } else { 1.0 };
+
+        let dimensions = [extent[0] as f32, extent[1] as f32];
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions,
+            depth_range: 0.0 .. 1.0,
+        };
+
+        let builder = GraphicsPipeline::start()
+            .vertex_input(vertex_input)
+            .vertex_shader(vertex_shader, ())
+            .primitive_restart(false)
+            .fragment_shader(fragment_shader, ())
+            .depth_clamp(false)
+            .line_width(line_width)
+            .blend_collective(self.blend_mode.attachment_blend())
+            .depth_stencil(self.depth_state.depth_stencil());
+
+        let builder = if self.dynamic_scissor {
+            builder.viewports_fixed_scissors_dynamic(vec![viewport])
+        } else {
+            builder.viewports(vec![viewport])
+        };
+
+        let builder = match self.topology {
+            Topology::PointList => builder.point_list(),
+            Topology::LineList => builder.line_list(),
+            Topology::LineStrip => builder.line_strip(),
+            Topology::TriangleList => builder.triangle_list(),
+            Topology::TriangleStrip => builder.triangle_strip(),
+        };
+
+        let builder = match self.polygon_mode {
+            PolygonMode::Fill => builder.polygon_mode_fill(),
+            PolygonMode::Line => builder.polygon_mode_line(),
+            PolygonMode::Point => builder.polygon_mode_point(),
+        };
+
+        let builder = match self.cull_mode {
+            CullMode::None => builder.cull_mode_disabled(),
+            CullMode::Front => builder.cull_mode_front(),
+            CullMode::Back => builder.cull_mode_back(),
+            CullMode::FrontAndBack => builder.cull_mode_front_and_back(),
+        };
+
+        let builder = match self.front_face {
+            FrontFace::Clockwise => builder.front_face_clockwise(),
+            FrontFace::CounterClockwise => builder.front_face_counter_clockwise(),
+        };
+
+        let pipeline = builder
+            .render_pass(subpass)
+            .build_with_cache(pipeline_cache.clone())
+            .build(device.clone())?;
+
+        Ok(Arc::new(pipeline))
+    }
+}
@@ -0,0 +1,224 @@
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::pipeline_desc::PipelineDesc;
+
+// A graphics pipeline as built by `PipelineDesc::build` for the vertex input `Vdef`.
+pub type Pipeline<Vdef> = GraphicsPipeline<
+                            Vdef,
+                            Box<dyn PipelineLayoutAbstract + Send + Sync + 'static>,
+                            Arc<dyn RenderPassAbstract + Send + Sync + 'static>
+                        >;
+
+pub type PipelineResult<Vdef = BufferlessDefinition> = Result<Arc<Pipeline<Vdef>>, GraphicsPipelineCreationError>;
+
+type AnyPipeline = Arc<dyn Any + Send + Sync>;
+
+type BuildPipeline = Box<dyn Fn(
+                        &Arc<Device>,
+                        &PipelineDesc,
+                        [u32; 2],
+                        Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+                        &Arc<PipelineCache>,
+                    ) -> Result<AnyPipeline, GraphicsPipelineCreationError>>;
+
+// Handle to a pipeline of the registry, typed by its vertex input so `get` hands back the
+// concrete pipeline that draw calls need.
+pub struct PipelineId<Vdef = BufferlessDefinition> {
+    index: usize,
+    vertex_input: PhantomData<fn() -> Vdef>,
+}
+
+impl<Vdef> PipelineId<Vdef> {
+    fn new(index: usize) -> Self {
+        Self { index, vertex_input: PhantomData }
+    }
+}
+
+impl<Vdef> Clone for PipelineId<Vdef> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Vdef> Copy for PipelineId<Vdef> {}
+
+impl<Vdef> PartialEq for PipelineId<Vdef> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<Vdef> Eq for PipelineId<Vdef> {}
+
+impl<Vdef> fmt::Debug for PipelineId<Vdef> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PipelineId").field(&self.index).finish()
+    }
+}
+
+struct Entry {
+    shaders: &'static str,
+    desc: PipelineDesc,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    subpass: u32,
+    build: BuildPipeline,
+    pipeline: AnyPipeline,
+}
+
+impl Entry {
+    fn matches(&self, shaders: &str, desc: &PipelineDesc, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, subpass: u32) -> bool {
+        self.shaders == shaders && self.desc == *desc && self.subpass == subpass
+            && Arc::ptr_eq(&self.render_pass, render_pass)
+    }
+}
+
+fn subpass(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, subpass: u32) -> Subpass<Arc<dyn RenderPassAbstract + Send + Sync>> {
+    Subpass::from(render_pass.clone(), subpass)
+        .expect("render pass has no such subpass")
+}
+
+fn erase<Vdef, F>(build: F) -> BuildPipeline
+where
+    Vdef: Send + Sync + 'static,
+    F: Fn(&Arc<Device>, &PipelineDesc, [u32; 2], Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>, &Arc<PipelineCache>) -> PipelineResult<Vdef> + 'static,
+{
+    Box::new(move |device, desc, extent, subpass, cache| {
+        build(device, desc, extent, subpass, cache).map(|pipeline| pipeline as AnyPipeline)
+    })
+}
+
+// A pipeline built for an entry of the registry but not installed yet.
+pub struct Replacement<Vdef = BufferlessDefinition> {
+    id: PipelineId<Vdef>,
+    build: BuildPipeline,
+    pipeline: AnyPipeline,
+}
+
+// Owns every graphics pipeline of a window. Pipelines are identified by a name for
+// their shaders plus their description, render pass and subpass, so asking twice for
+// the same combination returns the same pipeline. Since the viewport is baked into the
+// pipelines, all of them are rebuilt when the swap chain is recreated.
+pub struct PipelineRegistry {
+    device: Arc<Device>,
+    pipeline_cache: Arc<PipelineCache>,
+    extent: [u32; 2],
+    entries: Vec<Entry>,
+}
+
+impl PipelineRegistry {
+    pub fn new(device: &Arc<Device>, pipeline_cache: &Arc<PipelineCache>, extent: [u32; 2]) -> Self {
+        Self {
+            device: device.clone(),
+            pipeline_cache: pipeline_cache.clone(),
+            extent,
+            entries: Vec::new(),
+        }
+    }
+
+    // The extent the pipelines' viewports were built for.
+    pub fn extent(&self) -> [u32; 2] {
+        self.extent
+    }
+
+    pub fn get_or_create<Vdef, F>(
+        &mut self,
+        shaders: &'static str,
+        desc: &PipelineDesc,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        subpass_index: u32,
+        build: F,
+    ) -> PipelineId<Vdef>
+    where
+        Vdef: Send + Sync + 'static,
+        F: Fn(&Arc<Device>, &PipelineDesc, [u32; 2], Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>, &Arc<PipelineCache>) -> PipelineResult<Vdef> + 'static,
+    {
+        if let Some(index) = self.entries.iter().position(|entry| entry.matches(shaders, desc, render_pass, subpass_index)) {
+            return PipelineId::new(index)
+        }
+
+        let build = erase(build);
+        let pipeline = build(&self.device, desc, self.extent, subpass(render_pass, subpass_index), &self.pipeline_cache)
+            .unwrap_or_else(|err| panic!("failed to create {} pipeline: {:?}", shaders, err));
+
+        self.entries.push(Entry {
+            shaders,
+            desc: *desc,
+            render_pass: render_pass.clone(),
+            subpass: subpass_index,
+            build,
+            pipeline,
+        });
+
+        PipelineId::new(self.entries.len() - 1)
+    }
+
+    pub fn get<Vdef>(&self, id: PipelineId<Vdef>) -> Arc<Pipeline<Vdef>>
+    where
+        Vdef: Send + Sync + 'static,
+    {
+        self.entries[id.index].pipeline.clone()
+            .downcast()
+            .unwrap_or_else(|_| panic!("{} pipeline has another vertex input", self.entries[id.index].shaders))
+    }
+
+    // Builds a pipeline the way `build` does, to swap it in later with `replace`. The
+    // registry is left unchanged, so several replacements can be built before any of them
+    // is installed.
+    pub fn build_replacement<Vdef, F>(&self, id: PipelineId<Vdef>, build: F) -> Result<Replacement<Vdef>, GraphicsPipelineCreationError>
+    where
+        Vdef: Send + Sync + 'static,
+        F: Fn(&Arc<Device>, &PipelineDesc, [u32; 2], Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>, &Arc<PipelineCache>) -> PipelineResult<Vdef> + 'static,
+    {
+        let build = erase(build);
+        let entry = &self.entries[id.index];
+        let pipeline = build(&self.device, &entry.desc, self.extent, subpass(&entry.render_pass, entry.subpass), &self.pipeline_cache)?;
+
+        Ok(Replacement { id, build, pipeline })
+    }
+
+    // Swaps in a pipeline and the way it is built, e.g. after its shaders were reloaded.
+    pub fn replace<Vdef>(&mut self, replacement: Replacement<Vdef>) {
+        let entry = &mut self.entries[replacement.id.index];
+        entry.pipeline = replacement.pipeline;
+        entry.build = replacement.build;
+    }
+
+    // Moves the pipelines built for `old_render_pass` to `new_render_pass` and rebuilds
+    // every pipeline for the new extent. On failure the registry is left unchanged.
+    pub fn rebuild(
+        &mut self,
+        old_render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        new_render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        extent: [u32; 2],
+    ) -> Result<(), GraphicsPipelineCreationError> {
+        let mut rebuilt = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let render_pass = if Arc::ptr_eq(&entry.render_pass, old_render_pass) {
+                new_render_pass
+            } else {
+                &entry.render_pass
+            };
+
+            let pipeline = (entry.build)(&self.device, &entry.desc, extent, subpass(render_pass, entry.subpass), &self.pipeline_cache)?;
+            rebuilt.push((render_pass.clone(), pipeline));
+        }
+
+        for (entry, (render_pass, pipeline)) in self.entries.iter_mut().zip(rebuilt) {
+            entry.render_pass = render_pass;
+            entry.pipeline = pipeline;
+        }
+        self.extent = extent;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+
+use crate::pipeline_desc::BlendMode;
+
+// Orders the draws of a frame: opaque objects first, front-to-back to save fill rate,
+// then blended objects back-to-front so each one is composited over what lies behind
+// it. Draws at the same depth keep the order in which they were pushed.
+pub struct RenderQueue<T> {
+    opaque: Vec<(f32, T)>,
+    transparent: Vec<(f32, T)>,
+}
+
+impl<T> Default for RenderQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RenderQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            opaque: Vec::new(),
+            transparent: Vec::new(),
+        }
+    }
+
+    // `depth` is the distance along the view direction, as returned by `view_depth`.
+    pub fn push(&mut self, blend_mode: BlendMode, depth: f32, item: T) {
+        if blend_mode.is_transparent() {
+            self.transparent.push((depth, item));
+        } else {
+            self.opaque.push((depth, item));
+        }
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.opaque.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        self.transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+        self.opaque.drain(..)
+            .chain(self.transparent.drain(..))
+            .map(|(_, item)| item)
+    }
+}
+
+// Depth of a world space position in front of a camera with the given column-major
+// view matrix. The view space looks down -Z, so larger values are farther away.
+pub fn view_depth(view: &[[f32; 4]; 4], position: [f32; 3]) -> f32 {
+    let z = view[0][2] * position[0] + view[1][2] * position[1] + view[2][2] * position[2] + view[3][2];
+    -z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    fn drained<T>(queue: &mut RenderQueue<T>) -> Vec<T> {
+        queue.drain().collect()
+    }
+
+    #[test]
+    fn opaque_draws_before_transparent() {
+        let mut queue = RenderQueue::new();
+        queue.push(BlendMode::Alpha, 1.0, "alpha");
+        queue.push(BlendMode::Opaque, 10.0, "opaque");
+        queue.push(BlendMode::Additive, 5.0, "additive");
+
+        assert_eq!(drained(&mut queue), ["opaque", "additive", "alpha"]);
+    }
+
+    #[test]
+    fn opaque_sorted_near_to_far() {
+        let mut queue = RenderQueue::new();
+        queue.push(BlendMode::Opaque, 3.0, 3);
+        queue.push(BlendMode::Opaque, 1.0, 1);
+        queue.push(BlendMode::Opaque, 2.0, 2);
+
+        assert_eq!(drained(&mut queue), [1, 2, 3]);
+    }
+
+    #[test]
+    fn transparent_sorted_far_to_near() {
+        let mut queue = RenderQueue::new();
+        queue.push(BlendMode::Alpha, 1.0, 1);
+        queue.push(BlendMode::PremultipliedAlpha, 3.0, 3);
+        queue.push(BlendMode::Additive, 2.0, 2);
+
+        assert_eq!(drained(&mut queue), [3, 2, 1]);
+    }
+
+    #[test]
+    fn equal_depths_keep_push_order() {
+        let mut queue = RenderQueue::new();
+        queue.push(BlendMode::Alpha, 1.0, "first");
+        queue.push(BlendMode::Alpha, 1.0, "second");
+        queue.push(BlendMode::Opaque, 1.0, "third");
+        queue.push(BlendMode::Opaque, 1.0, "fourth");
+
+        assert_eq!(drained(&mut queue), ["third", "fourth", "first", "second"]);
+    }
+
+    #[test]
+    fn drain_empties_the_queue() {
+        let mut queue = RenderQueue::new();
+        queue.push(BlendMode::Opaque, 1.0, 1);
+        queue.push(BlendMode::Alpha, 1.0, 2);
+
+        assert_eq!(drained(&mut queue).len(), 2);
+        assert!(drained(&mut queue).is_empty());
+    }
+
+    #[test]
+    fn view_depth_grows_away_from_the_camera() {
+        // A camera at z = 5 looking down -Z.
+        let mut view = IDENTITY;
+        view[3][2] = -5.0;
+
+        assert_eq!(view_depth(&view, [0.0, 0.0, 0.0]), 5.0);
+        assert_eq!(view_depth(&view, [1.0, 2.0, -1.0]), 6.0);
+        assert!(view_depth(&view, [0.0, 0.0, 4.0]) < view_depth(&view, [0.0, 0.0, 0.0]));
+    }
+}
@@ -0,0 +1,410 @@
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{
+        GraphicsPipelineCreationError,
+        vertex::BufferlessDefinition,
+        cache::PipelineCache,
+    };
+use vulkano::pipeline::shader::{GraphicsShaderType, ShaderModule};
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use winit::event_loop::EventLoopProxy;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::pipeline_desc::PipelineDesc;
+use crate::pipeline_registry::PipelineResult;
+use crate::shader_reflect::{self, ReflectError, ShaderReflection, ShaderStage};
+
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+const INCLUDE_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+// Includes nested deeper than this are taken to be a cycle without include guards.
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+pub type ShaderDefines<'a> = &'a [(&'a str, Option<&'a str>)];
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(PathBuf, io::Error),
+    Compile(String),
+    Reflect(PathBuf, ReflectError),
+    WrongStage(PathBuf, ShaderStage),
+    Pipeline(GraphicsPipelineCreationError),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io(path, err) => write!(f, "failed to read {}: {}", path.display(), err),
+            ShaderError::Compile(message) => write!(f, "failed to compile shader: {}", message),
+            ShaderError::Reflect(path, err) => write!(f, "{}: {}", path.display(), err),
+            ShaderError::WrongStage(path, stage) => write!(f, "{} is a {:?} shader", path.display(), stage),
+            ShaderError::Pipeline(err) => write!(f, "failed to create graphics pipeline: {:?}", err),
+        }
+    }
+}
+
+pub struct RuntimeShader {
+    module: Arc<ShaderModule>,
+    reflection: ShaderReflection,
+    dependencies: Vec<PathBuf>,
+}
+
+impl RuntimeShader {
+    pub fn load(device: &Arc<Device>, path: &Path, defines: ShaderDefines) -> Result<Self, ShaderError> {
+        let (words, dependencies) = if path.extension().is_some_and(|ext| ext == "spv") {
+            (read_spirv(path)?, vec![path.to_owned()])
+        } else {
+            compile_glsl(path, defines)?
+        };
+
+        let reflection = shader_reflect::reflect(&words)
+            .map_err(|err| ShaderError::Reflect(path.to_owned(), err))?;
+
+        // The SPIR-V was either produced by shaderc or passed reflection, which is
+        // what vulkano_shaders::shader! relies on as well.
+        let module = unsafe { ShaderModule::from_words(device.clone(), &words) }
+            .map_err(|err| ShaderError::Compile(format!("{}: {:?}", path.display(), err)))?;
+
+        Ok(Self { module, reflection, dependencies })
+    }
+
+    fn expect_stage(self, path: &Path, stage: ShaderStage) -> Result<Self, ShaderError> {
+        if self.reflection.stage == stage {
+            Ok(self)
+        } else {
+            Err(ShaderError::WrongStage(path.to_owned(), self.reflection.stage))
+        }
+    }
+}
+
+fn shader_kind(path: &Path) -> Result<shaderc::ShaderKind, ShaderError> {
+    let kind = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("vert") => shaderc::ShaderKind::Vertex,
+        Some("frag") => shaderc::ShaderKind::Fragment,
+        Some("comp") => shaderc::ShaderKind::Compute,
+        Some("geom") => shaderc::ShaderKind::Geometry,
+        Some("tesc") => shaderc::ShaderKind::TessControl,
+        Some("tese") => shaderc::ShaderKind::TessEvaluation,
+        _ => return Err(ShaderError::Compile(format!("unknown shader stage for {}", path.display()))),
+    };
+
+    Ok(kind)
+}
+
+// Quoted includes are looked up next to the including file first, then in the include
+// root; angle-bracket includes only in the include root.
+fn resolve_include(requested: &str, include_type: shaderc::IncludeType, requesting: &str) -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let shaderc::IncludeType::Relative = include_type {
+        if let Some(parent) = Path::new(requesting).parent() {
+            candidates.push(parent.join(requested));
+        }
+    }
+    candidates.push(Path::new(INCLUDE_ROOT).join(requested));
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+fn compile_glsl(path: &Path, defines: ShaderDefines) -> Result<(Vec<u32>, Vec<PathBuf>), ShaderError> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| ShaderError::Io(path.to_owned(), err))?;
+
+    let mut compiler = shaderc::Compiler::new()
+        .ok_or_else(|| ShaderError::Compile("failed to initialise shaderc".into()))?;
+
+    let dependencies = RefCell::new(vec![path.to_owned()]);
+
+    let artifact = {
+        let mut options = shaderc::CompileOptions::new()
+            .ok_or_else(|| ShaderError::Compile("failed to initialise shaderc options".into()))?;
+
+        for (name, value) in defines {
+            options.add_macro_definition(name, *value);
+        }
+
+        options.set_include_callback(|requested, include_type, requesting, depth| {
+            if depth > MAX_INCLUDE_DEPTH {
+                return Err(format!("include cycle through \"{}\" from {}", requested, requesting))
+            }
+
+            let resolved = resolve_include(requested, include_type, requesting)
+                .ok_or_else(|| format!("cannot find include \"{}\" from {}", requested, requesting))?;
+            let content = fs::read_to_string(&resolved)
+                .map_err(|err| format!("failed to read {}: {}", resolved.display(), err))?;
+
+            dependencies.borrow_mut().push(resolved.clone());
+            Ok(shaderc::ResolvedInclude {
+                resolved_name: resolved.to_string_lossy().into_owned(),
+                content,
+            })
+        });
+
+        compiler.compile_into_spirv(
+                    &source,
+                    shader_kind(path)?,
+                    &path.to_string_lossy(),
+                    "main",
+                    Some(&options)
+                ).map_err(|err| ShaderError::Compile(err.to_string()))?
+    };
+
+    if artifact.get_num_warnings() > 0 {
+        println!("{}", artifact.get_warning_messages());
+    }
+
+    Ok((artifact.as_binary().to_vec(), dependencies.into_inner()))
+}
+
+fn read_spirv(path: &Path) -> Result<Vec<u32>, ShaderError> {
+    let bytes = fs::read(path)
+        .map_err(|err| ShaderError::Io(path.to_owned(), err))?;
+
+    if bytes.len() % 4 != 0 {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "SPIR-V size is not a multiple of 4");
+        return Err(ShaderError::Io(path.to_owned(), err))
+    }
+
+    Ok(bytes.chunks(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect())
+}
+
+pub struct RuntimeShaderProgram {
+    vertex: RuntimeShader,
+    fragment: RuntimeShader,
+}
+
+impl RuntimeShaderProgram {
+    pub fn load(device: &Arc<Device>, vertex_path: &Path, fragment_path: &Path, defines: ShaderDefines) -> Result<Self, ShaderError> {
+        Ok(Self {
+            vertex: RuntimeShader::load(device, vertex_path, defines)?
+                .expect_stage(vertex_path, ShaderStage::Vertex)?,
+            fragment: RuntimeShader::load(device, fragment_path, defines)?
+                .expect_stage(fragment_path, ShaderStage::Fragment)?,
+        })
+    }
+
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        let mut dependencies = self.vertex.dependencies.clone();
+        for path in &self.fragment.dependencies {
+            if !dependencies.contains(path) {
+                dependencies.push(path.clone());
+            }
+        }
+
+        dependencies
+    }
+
+    pub fn create_graphics_pipeline(
+        &self,
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult {
+        let vertex = &self.vertex.reflection;
+        let fragment = &self.fragment.reflection;
+
+        let vertex_entry_point = unsafe {
+            self.vertex.module.graphics_entry_point::<(), _, _, _>(
+                &vertex.entry_point,
+                vertex.inputs.clone(),
+                vertex.outputs.clone(),
+                vertex.layout.clone(),
+                GraphicsShaderType::Vertex
+            )
+        };
+
+        let fragment_entry_point = unsafe {
+            self.fragment.module.graphics_entry_point::<(), _, _, _>(
+                &fragment.entry_point,
+                fragment.inputs.clone(),
+                fragment.outputs.clone(),
+                fragment.layout.clone(),
+                GraphicsShaderType::Fragment
+            )
+        };
+
+        desc.build(
+                device,
+                BufferlessDefinition {},
+                vertex_entry_point,
+                fragment_entry_point,
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
+    }
+}
+
+pub struct ShaderWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    directories: Vec<PathBuf>,
+    paths: Vec<PathBuf>,
+}
+
+impl ShaderWatcher {
+    // Wakes the event loop with a user event whenever the watcher has news, as it may be
+    // waiting for input.
+    pub fn new(paths: &[PathBuf], event_loop: EventLoopProxy<()>) -> notify::Result<Self> {
+        let (sender, notifications) = mpsc::channel();
+        let watcher: RecommendedWatcher = Watcher::new(sender, WATCH_DEBOUNCE)?;
+
+        // Ends once the watcher is dropped or the event loop is gone.
+        let (forward, events) = mpsc::channel();
+        thread::spawn(move || {
+            for event in notifications {
+                if forward.send(event).is_err() || event_loop.send_event(()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut shader_watcher = Self {
+            watcher,
+            events,
+            directories: Vec::new(),
+            paths: Vec::new(),
+        };
+        shader_watcher.watch(paths)?;
+
+        Ok(shader_watcher)
+    }
+
+    // Watches the parent directories, since editors often replace files on save
+    // and a watch on the file itself would be lost.
+    pub fn watch(&mut self, paths: &[PathBuf]) -> notify::Result<()> {
+        let mut watched = Vec::new();
+        for path in paths {
+            let path = fs::canonicalize(path)?;
+            if let Some(parent) = path.parent() {
+                if !self.directories.iter().any(|directory| directory == parent) {
+                    self.watcher.watch(parent, RecursiveMode::NonRecursive)?;
+                    self.directories.push(parent.to_owned());
+                }
+            }
+            watched.push(path);
+        }
+
+        self.paths = watched;
+        Ok(())
+    }
+
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            let path = match event {
+                DebouncedEvent::Create(path) |
+                DebouncedEvent::Write(path) |
+                DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+
+            changed |= self.paths.iter().any(|watched| {
+                *watched == path || fs::canonicalize(&path).is_ok_and(|path| *watched == path)
+            });
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+    // A fresh directory under the system temp directory, filled with `files`.
+    fn shader_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("runtime_shaders_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    const MAIN: &str = "
+        #version 450
+        #extension GL_GOOGLE_include_directive : require
+        #include \"first.glsl\"
+        layout(location = 0) out vec4 color;
+        void main() { color = vec4(first()); }
+    ";
+
+    #[test]
+    fn relative_include_prefers_the_including_directory() {
+        let dir = shader_dir("relative", &[("shader_common.glsl", "")]);
+        let requesting = dir.join("main.frag");
+
+        let resolved = resolve_include("shader_common.glsl", shaderc::IncludeType::Relative, &requesting.to_string_lossy());
+        assert_eq!(resolved, Some(dir.join("shader_common.glsl")));
+
+        let resolved = resolve_include("shader_common.glsl", shaderc::IncludeType::Standard, &requesting.to_string_lossy());
+        assert_eq!(resolved, Some(Path::new(INCLUDE_ROOT).join("shader_common.glsl")));
+    }
+
+    #[test]
+    fn relative_include_falls_back_to_the_include_root() {
+        let dir = shader_dir("fallback", &[]);
+        let requesting = dir.join("main.frag");
+
+        let resolved = resolve_include("shader_common.glsl", shaderc::IncludeType::Relative, &requesting.to_string_lossy());
+        assert_eq!(resolved, Some(Path::new(INCLUDE_ROOT).join("shader_common.glsl")));
+    }
+
+    #[test]
+    fn missing_include_is_not_resolved() {
+        let dir = shader_dir("missing", &[]);
+        let requesting = dir.join("main.frag");
+
+        assert_eq!(resolve_include("nowhere.glsl", shaderc::IncludeType::Relative, &requesting.to_string_lossy()), None);
+    }
+
+    #[test]
+    fn nested_includes_are_compiled_and_tracked() {
+        let dir = shader_dir("nested", &[
+            ("main.frag", MAIN),
+            ("first.glsl", "#include \"lib/second.glsl\"\nfloat first() { return second(); }\n"),
+            ("lib/second.glsl", "float second() { return VALUE; }\n"),
+        ]);
+
+        let (words, dependencies) = compile_glsl(&dir.join("main.frag"), &[("VALUE", Some("0.5"))]).unwrap();
+        assert!(!words.is_empty());
+        assert_eq!(dependencies, vec![dir.join("main.frag"), dir.join("first.glsl"), dir.join("lib/second.glsl")]);
+    }
+
+    #[test]
+    fn missing_include_fails_to_compile() {
+        let dir = shader_dir("missing_compile", &[("main.frag", MAIN)]);
+
+        let err = compile_glsl(&dir.join("main.frag"), &[]).expect_err("missing include should not compile");
+        assert!(err.to_string().contains("cannot find include \"first.glsl\""), "{}", err);
+    }
+
+    #[test]
+    fn include_cycle_fails_to_compile() {
+        let dir = shader_dir("cycle", &[
+            ("main.frag", MAIN),
+            ("first.glsl", "#include \"second.glsl\"\nfloat first() { return 1.0; }\n"),
+            ("second.glsl", "#include \"first.glsl\"\n"),
+        ]);
+
+        let err = compile_glsl(&dir.join("main.frag"), &[]).expect_err("include cycle should not compile");
+        assert!(err.to_string().contains("include cycle"), "{}", err);
+    }
+}
@@ -1,10 +1,18 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "shader_common.glsl"
 
 out gl_PerVertex {
     vec4 gl_Position;
 };
 
+layout(set = 0, binding = 0) uniform CameraUniforms {
+    mat4 view;
+    mat4 projection;
+} camera;
+
 layout(location = 0) out vec3 fragColor;
 
 vec2 positions[3] = vec2[](
@@ -20,6 +28,12 @@ vec3 colors[3] = vec3[](
 );
 
 void main() {
-    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
+    // The positions are in Vulkan's Y-down clip space, world space has Y pointing up.
+    vec2 position = positions[gl_VertexIndex] * vec2(1.0, -1.0);
+    gl_Position = camera.projection * camera.view * vec4(position, 0.0, 1.0);
+#ifdef PALETTE_COLORS
+    fragColor = palette(float(gl_VertexIndex) / 3.0);
+#else
     fragColor = colors[gl_VertexIndex];
+#endif
 }
\ No newline at end of file
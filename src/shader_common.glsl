@@ -0,0 +1,18 @@
+#ifndef SHADER_COMMON_GLSL
+#define SHADER_COMMON_GLSL
+
+struct Vertex {
+    vec4 position;
+    vec4 color;
+};
+
+struct Particle {
+    vec2 position;
+    vec2 velocity;
+};
+
+vec3 palette(float t) {
+    return 0.5 + 0.5 * cos(6.2831853 * (t + vec3(0.0, 0.33, 0.67)));
+}
+
+#endif
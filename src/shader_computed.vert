@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "shader_common.glsl"
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(set = 0, binding = 0) readonly buffer Vertices {
+    Vertex vertices[];
+};
+
+layout(location = 0) out vec3 fragColor;
+
+void main() {
+    gl_Position = vertices[gl_VertexIndex].position;
+    fragColor = vertices[gl_VertexIndex].color.rgb;
+}
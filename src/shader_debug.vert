@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+} push;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+
+layout(location = 0) out vec4 fragColor;
+
+void main() {
+    gl_Position = push.view_projection * vec4(position, 1.0);
+    fragColor = color;
+}
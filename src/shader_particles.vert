@@ -0,0 +1,38 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "shader_common.glsl"
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(set = 0, binding = 0) readonly buffer Particles {
+    Particle particles[];
+};
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragOffset;
+
+const float PARTICLE_SIZE = 0.006;
+
+vec2 corners[6] = vec2[](
+    vec2(-1.0, -1.0),
+    vec2(1.0, -1.0),
+    vec2(1.0, 1.0),
+    vec2(-1.0, -1.0),
+    vec2(1.0, 1.0),
+    vec2(-1.0, 1.0)
+);
+
+void main() {
+    Particle particle = particles[gl_InstanceIndex];
+    vec2 corner = corners[gl_VertexIndex];
+
+    gl_Position = vec4(particle.position + PARTICLE_SIZE * corner, 0.0, 1.0);
+    fragOffset = corner;
+
+    float speed = clamp(length(particle.velocity), 0.0, 1.0);
+    fragColor = mix(vec3(0.1, 0.3, 1.0), vec3(1.0, 0.5, 0.1), speed);
+}
@@ -0,0 +1,776 @@
+use vulkano::descriptor::descriptor::{
+        DescriptorBufferDesc,
+        DescriptorDesc,
+        DescriptorDescTy,
+        DescriptorImageDesc,
+        DescriptorImageDescArray,
+        DescriptorImageDescDimensions,
+        ShaderStages,
+    };
+use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
+use vulkano::format::Format;
+use vulkano::pipeline::shader::{ShaderInterfaceDef, ShaderInterfaceDefEntry};
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt;
+use std::vec::IntoIter;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+const OP_NAME: u16 = 5;
+const OP_ENTRY_POINT: u16 = 15;
+const OP_TYPE_BOOL: u16 = 20;
+const OP_TYPE_INT: u16 = 21;
+const OP_TYPE_FLOAT: u16 = 22;
+const OP_TYPE_VECTOR: u16 = 23;
+const OP_TYPE_MATRIX: u16 = 24;
+const OP_TYPE_IMAGE: u16 = 25;
+const OP_TYPE_SAMPLER: u16 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+const OP_TYPE_ARRAY: u16 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+const OP_TYPE_STRUCT: u16 = 30;
+const OP_TYPE_POINTER: u16 = 32;
+const OP_CONSTANT: u16 = 43;
+const OP_SPEC_CONSTANT: u16 = 50;
+const OP_SPEC_CONSTANT_OP: u16 = 52;
+const OP_VARIABLE: u16 = 59;
+const OP_DECORATE: u16 = 71;
+const OP_MEMBER_DECORATE: u16 = 72;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_NON_WRITABLE: u32 = 24;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_OUTPUT: u32 = 3;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    TessellationControl,
+    TessellationEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    fn from_execution_model(model: u32) -> Option<Self> {
+        match model {
+            0 => Some(ShaderStage::Vertex),
+            1 => Some(ShaderStage::TessellationControl),
+            2 => Some(ShaderStage::TessellationEvaluation),
+            3 => Some(ShaderStage::Geometry),
+            4 => Some(ShaderStage::Fragment),
+            5 => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    pub fn stages(self) -> ShaderStages {
+        ShaderStages {
+            vertex: self == ShaderStage::Vertex,
+            tessellation_control: self == ShaderStage::TessellationControl,
+            tessellation_evaluation: self == ShaderStage::TessellationEvaluation,
+            geometry: self == ShaderStage::Geometry,
+            fragment: self == ShaderStage::Fragment,
+            compute: self == ShaderStage::Compute,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReflectError(String);
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid SPIR-V: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeInterface {
+    entries: Vec<ShaderInterfaceDefEntry>,
+}
+
+unsafe impl ShaderInterfaceDef for RuntimeInterface {
+    type Iter = IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.entries.clone().into_iter()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeLayout {
+    sets: Vec<Vec<Option<DescriptorDesc>>>,
+    push_constants: Option<PipelineLayoutDescPcRange>,
+}
+
+unsafe impl PipelineLayoutDesc for RuntimeLayout {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(|bindings| bindings.len())
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets.get(set)?.get(binding)?.clone()
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        if self.push_constants.is_some() { 1 } else { 0 }
+    }
+
+    fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
+        if num == 0 { self.push_constants } else { None }
+    }
+}
+
+pub struct ShaderReflection {
+    pub entry_point: CString,
+    pub stage: ShaderStage,
+    pub inputs: RuntimeInterface,
+    pub outputs: RuntimeInterface,
+    pub layout: RuntimeLayout,
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, arrayed: bool, multisampled: bool, sampled: u32 },
+    Sampler,
+    SampledImage { image: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+#[derive(Default)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    built_in: bool,
+    buffer_block: bool,
+    array_stride: Option<u32>,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    built_in: bool,
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    non_writable: bool,
+}
+
+struct Variable {
+    id: u32,
+    pointer_type: u32,
+    storage_class: u32,
+}
+
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    entry_point: Option<(u32, String, Vec<u32>)>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    spec_constant_ops: HashSet<u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    variables: Vec<Variable>,
+}
+
+pub fn reflect(words: &[u32]) -> Result<ShaderReflection, ReflectError> {
+    let module = parse(words)?;
+
+    let (model, name, interface) = module.entry_point.clone()
+        .ok_or_else(|| ReflectError("no entry point".into()))?;
+    let stage = ShaderStage::from_execution_model(model)
+        .ok_or_else(|| ReflectError(format!("unsupported execution model {}", model)))?;
+
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut sets: Vec<Vec<Option<DescriptorDesc>>> = Vec::new();
+    let mut push_constants = None;
+
+    for variable in &module.variables {
+        let pointee = module.pointee(variable.pointer_type)?;
+        let decorations = module.decorations.get(&variable.id);
+
+        match variable.storage_class {
+            STORAGE_CLASS_INPUT | STORAGE_CLASS_OUTPUT => {
+                if !interface.contains(&variable.id) || module.is_built_in(variable.id, pointee) {
+                    continue;
+                }
+
+                let location = decorations.and_then(|d| d.location)
+                    .ok_or_else(|| ReflectError(format!("interface variable {} has no location", variable.id)))?;
+                let (format, locations) = module.interface_format(pointee)?;
+                let entry = ShaderInterfaceDefEntry {
+                    location: location .. location + locations,
+                    format,
+                    name: module.names.get(&variable.id).map(|name| Cow::Owned(name.clone())),
+                };
+
+                if variable.storage_class == STORAGE_CLASS_INPUT {
+                    inputs.push(entry);
+                } else {
+                    outputs.push(entry);
+                }
+            },
+            STORAGE_CLASS_UNIFORM | STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_STORAGE_BUFFER => {
+                let set = decorations.and_then(|d| d.set).unwrap_or(0) as usize;
+                let binding = decorations.and_then(|d| d.binding).unwrap_or(0) as usize;
+                let descriptor = module.descriptor(variable.storage_class, pointee, stage)?;
+
+                if sets.len() <= set {
+                    sets.resize(set + 1, Vec::new());
+                }
+                if sets[set].len() <= binding {
+                    sets[set].resize(binding + 1, None);
+                }
+                sets[set][binding] = Some(descriptor);
+            },
+            STORAGE_CLASS_PUSH_CONSTANT => {
+                push_constants = Some(PipelineLayoutDescPcRange {
+                    offset: 0,
+                    size: module.size_of(pointee, None)? as usize,
+                    stages: stage.stages(),
+                });
+            },
+            _ => (),
+        }
+    }
+
+    inputs.sort_by_key(|entry| entry.location.start);
+    outputs.sort_by_key(|entry| entry.location.start);
+
+    Ok(ShaderReflection {
+        entry_point: CString::new(name).map_err(|_| ReflectError("entry point name contains a nul byte".into()))?,
+        stage,
+        inputs: RuntimeInterface { entries: inputs },
+        outputs: RuntimeInterface { entries: outputs },
+        layout: RuntimeLayout { sets, push_constants },
+    })
+}
+
+fn parse(words: &[u32]) -> Result<Module, ReflectError> {
+    if words.len() < 5 || words[0] != SPIRV_MAGIC {
+        return Err(ReflectError("bad header".into()))
+    }
+
+    let mut module = Module::default();
+    let mut offset = 5;
+
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        let opcode = (words[offset] & 0xffff) as u16;
+        if word_count == 0 || offset + word_count > words.len() {
+            return Err(ReflectError(format!("truncated instruction at word {}", offset)))
+        }
+
+        let operands = &words[offset + 1 .. offset + word_count];
+        module.instruction(opcode, operands)?;
+        offset += word_count;
+    }
+
+    Ok(module)
+}
+
+fn parse_string(operands: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, word) in operands.iter().enumerate() {
+        for byte in word.to_le_bytes().iter() {
+            if *byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1)
+            }
+            bytes.push(*byte);
+        }
+    }
+
+    (String::from_utf8_lossy(&bytes).into_owned(), operands.len())
+}
+
+fn operand(operands: &[u32], index: usize) -> Result<u32, ReflectError> {
+    operands.get(index).copied()
+        .ok_or_else(|| ReflectError("missing operand".into()))
+}
+
+impl Module {
+    fn instruction(&mut self, opcode: u16, operands: &[u32]) -> Result<(), ReflectError> {
+        match opcode {
+            OP_NAME => {
+                let (name, _) = parse_string(operands.get(1..).unwrap_or(&[]));
+                self.names.insert(operand(operands, 0)?, name);
+            },
+            OP_ENTRY_POINT if self.entry_point.is_none() => {
+                let (name, name_words) = parse_string(operands.get(2..).unwrap_or(&[]));
+                let interface = operands.get(2 + name_words..).unwrap_or(&[]).to_vec();
+                self.entry_point = Some((operand(operands, 0)?, name, interface));
+            },
+            OP_TYPE_BOOL => {
+                self.types.insert(operand(operands, 0)?, Type::Bool);
+            },
+            OP_TYPE_INT => {
+                self.types.insert(operand(operands, 0)?, Type::Int {
+                    width: operand(operands, 1)?,
+                    signed: operand(operands, 2)? != 0,
+                });
+            },
+            OP_TYPE_FLOAT => {
+                self.types.insert(operand(operands, 0)?, Type::Float { width: operand(operands, 1)? });
+            },
+            OP_TYPE_VECTOR => {
+                self.types.insert(operand(operands, 0)?, Type::Vector {
+                    component: operand(operands, 1)?,
+                    count: operand(operands, 2)?,
+                });
+            },
+            OP_TYPE_MATRIX => {
+                self.types.insert(operand(operands, 0)?, Type::Matrix {
+                    column: operand(operands, 1)?,
+                    count: operand(operands, 2)?,
+                });
+            },
+            OP_TYPE_IMAGE => {
+                self.types.insert(operand(operands, 0)?, Type::Image {
+                    dim: operand(operands, 2)?,
+                    arrayed: operand(operands, 4)? != 0,
+                    multisampled: operand(operands, 5)? != 0,
+                    sampled: operand(operands, 6)?,
+                });
+            },
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(operands, 0)?, Type::Sampler);
+            },
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(operands, 0)?, Type::SampledImage { image: operand(operands, 1)? });
+            },
+            OP_TYPE_ARRAY => {
+                let length_id = operand(operands, 2)?;
+                if self.spec_constant_ops.contains(&length_id) {
+                    return Err(ReflectError(format!(
+                        "array length {} is a specialization constant expression, which runtime shaders do not support",
+                        length_id
+                    )))
+                }
+                let length = *self.constants.get(&length_id)
+                    .ok_or_else(|| ReflectError(format!("array length {} is not a constant", length_id)))?;
+                self.types.insert(operand(operands, 0)?, Type::Array { element: operand(operands, 1)?, length });
+            },
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(operand(operands, 0)?, Type::RuntimeArray);
+            },
+            OP_TYPE_STRUCT => {
+                self.types.insert(operand(operands, 0)?, Type::Struct { members: operands.get(1..).unwrap_or(&[]).to_vec() });
+            },
+            OP_TYPE_POINTER => {
+                self.types.insert(operand(operands, 0)?, Type::Pointer { pointee: operand(operands, 2)? });
+            },
+            OP_CONSTANT => {
+                self.constants.insert(operand(operands, 1)?, operand(operands, 2)?);
+            },
+            // Runtime pipelines are built without specialization data, so specialization
+            // constants keep their default value.
+            OP_SPEC_CONSTANT => {
+                self.constants.insert(operand(operands, 1)?, operand(operands, 2)?);
+            },
+            OP_SPEC_CONSTANT_OP => {
+                self.spec_constant_ops.insert(operand(operands, 1)?);
+            },
+            OP_VARIABLE => {
+                self.variables.push(Variable {
+                    pointer_type: operand(operands, 0)?,
+                    id: operand(operands, 1)?,
+                    storage_class: operand(operands, 2)?,
+                });
+            },
+            OP_DECORATE => {
+                let decorations = self.decorations.entry(operand(operands, 0)?).or_default();
+                match operand(operands, 1)? {
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = Some(operand(operands, 2)?),
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    DECORATION_LOCATION => decorations.location = Some(operand(operands, 2)?),
+                    DECORATION_BINDING => decorations.binding = Some(operand(operands, 2)?),
+                    DECORATION_DESCRIPTOR_SET => decorations.set = Some(operand(operands, 2)?),
+                    _ => (),
+                }
+            },
+            OP_MEMBER_DECORATE => {
+                let key = (operand(operands, 0)?, operand(operands, 1)?);
+                let decorations = self.member_decorations.entry(key).or_default();
+                match operand(operands, 2)? {
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    DECORATION_OFFSET => decorations.offset = Some(operand(operands, 3)?),
+                    DECORATION_MATRIX_STRIDE => decorations.matrix_stride = Some(operand(operands, 3)?),
+                    DECORATION_NON_WRITABLE => decorations.non_writable = true,
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn ty(&self, id: u32) -> Result<&Type, ReflectError> {
+        self.types.get(&id)
+            .ok_or_else(|| ReflectError(format!("unknown type {}", id)))
+    }
+
+    fn pointee(&self, pointer_type: u32) -> Result<u32, ReflectError> {
+        match self.ty(pointer_type)? {
+            Type::Pointer { pointee } => Ok(*pointee),
+            _ => Err(ReflectError(format!("variable type {} is not a pointer", pointer_type))),
+        }
+    }
+
+    fn is_built_in(&self, variable: u32, pointee: u32) -> bool {
+        if self.decorations.get(&variable).is_some_and(|d| d.built_in) {
+            return true
+        }
+
+        let mut ty = pointee;
+        while let Some(Type::Array { element, .. }) = self.types.get(&ty) {
+            ty = *element;
+        }
+
+        match self.types.get(&ty) {
+            Some(Type::Struct { members }) => (0..members.len() as u32)
+                .any(|member| self.member_decorations.get(&(ty, member)).is_some_and(|d| d.built_in)),
+            _ => false,
+        }
+    }
+
+    fn interface_format(&self, ty: u32) -> Result<(Format, u32), ReflectError> {
+        match self.ty(ty)? {
+            Type::Array { element, length } => {
+                let (format, locations) = self.interface_format(*element)?;
+                Ok((format, locations * length))
+            },
+            Type::Matrix { column, count } => {
+                let (format, _) = self.interface_format(*column)?;
+                Ok((format, *count))
+            },
+            Type::Vector { component, count } => Ok((self.scalar_format(*component, *count)?, 1)),
+            _ => Ok((self.scalar_format(ty, 1)?, 1)),
+        }
+    }
+
+    fn scalar_format(&self, ty: u32, count: u32) -> Result<Format, ReflectError> {
+        let format = match (self.ty(ty)?, count) {
+            (Type::Float { width: 32 }, 1) => Format::R32Sfloat,
+            (Type::Float { width: 32 }, 2) => Format::R32G32Sfloat,
+            (Type::Float { width: 32 }, 3) => Format::R32G32B32Sfloat,
+            (Type::Float { width: 32 }, 4) => Format::R32G32B32A32Sfloat,
+            (Type::Int { width: 32, signed: true }, 1) => Format::R32Sint,
+            (Type::Int { width: 32, signed: true }, 2) => Format::R32G32Sint,
+            (Type::Int { width: 32, signed: true }, 3) => Format::R32G32B32Sint,
+            (Type::Int { width: 32, signed: true }, 4) => Format::R32G32B32A32Sint,
+            (Type::Int { width: 32, signed: false }, 1) => Format::R32Uint,
+            (Type::Int { width: 32, signed: false }, 2) => Format::R32G32Uint,
+            (Type::Int { width: 32, signed: false }, 3) => Format::R32G32B32Uint,
+            (Type::Int { width: 32, signed: false }, 4) => Format::R32G32B32A32Uint,
+            (other, _) => return Err(ReflectError(format!("unsupported interface type {:?}", other))),
+        };
+
+        Ok(format)
+    }
+
+    fn descriptor(&self, storage_class: u32, pointee: u32, stage: ShaderStage) -> Result<DescriptorDesc, ReflectError> {
+        let mut ty = pointee;
+        let mut array_count = 1;
+        if let Type::Array { element, length } = self.ty(ty)? {
+            ty = *element;
+            array_count = *length;
+        }
+
+        let decorations = self.decorations.get(&ty);
+        let (descriptor_ty, readonly) = match self.ty(ty)? {
+            Type::Struct { members } => {
+                let storage = storage_class == STORAGE_CLASS_STORAGE_BUFFER
+                    || decorations.is_some_and(|d| d.buffer_block);
+                let readonly = !storage || (0..members.len() as u32)
+                    .all(|member| self.member_decorations.get(&(ty, member)).is_some_and(|d| d.non_writable));
+
+                (DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage }), readonly)
+            },
+            Type::SampledImage { image } => (DescriptorDescTy::CombinedImageSampler(self.image_desc(*image)?), true),
+            Type::Image { sampled, .. } => {
+                let desc = self.image_desc(ty)?;
+                (DescriptorDescTy::Image(desc), *sampled != 2)
+            },
+            Type::Sampler => (DescriptorDescTy::Sampler, true),
+            other => return Err(ReflectError(format!("unsupported descriptor type {:?}", other))),
+        };
+
+        Ok(DescriptorDesc {
+            ty: descriptor_ty,
+            array_count,
+            stages: stage.stages(),
+            readonly,
+        })
+    }
+
+    fn image_desc(&self, image: u32) -> Result<DescriptorImageDesc, ReflectError> {
+        match self.ty(image)? {
+            Type::Image { dim, arrayed, multisampled, sampled } => {
+                let dimensions = match dim {
+                    0 => DescriptorImageDescDimensions::OneDimensional,
+                    1 => DescriptorImageDescDimensions::TwoDimensional,
+                    2 => DescriptorImageDescDimensions::ThreeDimensional,
+                    3 => DescriptorImageDescDimensions::Cube,
+                    other => return Err(ReflectError(format!("unsupported image dimension {}", other))),
+                };
+
+                Ok(DescriptorImageDesc {
+                    sampled: *sampled != 2,
+                    dimensions,
+                    format: None,
+                    multisampled: *multisampled,
+                    array_layers: if *arrayed {
+                        DescriptorImageDescArray::Arrayed { max_layers: None }
+                    } else {
+                        DescriptorImageDescArray::NonArrayed
+                    },
+                })
+            },
+            other => Err(ReflectError(format!("expected an image type, found {:?}", other))),
+        }
+    }
+
+    fn size_of(&self, ty: u32, matrix_stride: Option<u32>) -> Result<u32, ReflectError> {
+        let size = match self.ty(ty)? {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => self.size_of(*component, None)? * count,
+            Type::Matrix { column, count } => {
+                let stride = match matrix_stride {
+                    Some(stride) => stride,
+                    None => self.size_of(*column, None)?,
+                };
+                stride * count
+            },
+            Type::Array { element, length } => {
+                let stride = match self.decorations.get(&ty).and_then(|d| d.array_stride) {
+                    Some(stride) => stride,
+                    None => self.size_of(*element, None)?,
+                };
+                stride * length
+            },
+            Type::Struct { members } => {
+                let mut size = 0;
+                for (index, member) in members.iter().enumerate() {
+                    let decorations = self.member_decorations.get(&(ty, index as u32));
+                    let offset = decorations.and_then(|d| d.offset).unwrap_or(size);
+                    let member_size = self.size_of(*member, decorations.and_then(|d| d.matrix_stride))?;
+                    size = size.max(offset + member_size);
+                }
+                size
+            },
+            other => return Err(ReflectError(format!("cannot compute the size of {:?}", other))),
+        };
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str, kind: shaderc::ShaderKind) -> Vec<u32> {
+        let mut compiler = shaderc::Compiler::new().expect("failed to initialise shaderc");
+        compiler.compile_into_spirv(source, kind, "test.glsl", "main", None)
+            .expect("failed to compile test shader")
+            .as_binary()
+            .to_vec()
+    }
+
+    fn interface(entries: &RuntimeInterface) -> Vec<(u32, u32, Format, &str)> {
+        entries.entries.iter()
+            .map(|entry| (
+                entry.location.start,
+                entry.location.end,
+                entry.format,
+                entry.name.as_deref().unwrap_or(""),
+            ))
+            .collect()
+    }
+
+    fn descriptor(layout: &RuntimeLayout, set: usize, binding: usize) -> DescriptorDesc {
+        layout.descriptor(set, binding).expect("missing descriptor")
+    }
+
+    #[test]
+    fn rejects_words_that_are_not_spirv() {
+        assert!(reflect(&[]).is_err());
+        assert!(reflect(&[0xdead_beef, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn reflects_vertex_inputs_and_outputs() {
+        let words = compile("
+            #version 450
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec2 uv;
+            layout(location = 2) in mat4 model;
+            layout(location = 0) out vec2 v_uv;
+            layout(location = 1) flat out ivec2 v_id;
+
+            void main() {
+                v_uv = uv;
+                v_id = ivec2(gl_VertexIndex);
+                gl_Position = model * vec4(position, 1.0);
+            }
+        ", shaderc::ShaderKind::Vertex);
+
+        let reflection = reflect(&words).unwrap();
+        assert_eq!(reflection.stage, ShaderStage::Vertex);
+        assert_eq!(reflection.entry_point.to_str(), Ok("main"));
+
+        // gl_VertexIndex and gl_Position are built-ins and not part of the interface.
+        assert_eq!(interface(&reflection.inputs), vec![
+            (0, 1, Format::R32G32B32Sfloat, "position"),
+            (1, 2, Format::R32G32Sfloat, "uv"),
+            (2, 6, Format::R32G32B32A32Sfloat, "model"),
+        ]);
+        assert_eq!(interface(&reflection.outputs), vec![
+            (0, 1, Format::R32G32Sfloat, "v_uv"),
+            (1, 2, Format::R32G32Sint, "v_id"),
+        ]);
+        assert_eq!(reflection.layout.num_sets(), 0);
+        assert!(reflection.layout.push_constants.is_none());
+    }
+
+    #[test]
+    fn reflects_descriptor_bindings() {
+        let words = compile("
+            #version 450
+            layout(set = 0, binding = 0) uniform Globals { vec4 tint; } globals;
+            layout(set = 0, binding = 2) uniform sampler2D textures[3];
+            layout(set = 1, binding = 0) readonly buffer Lights { vec4 lights[]; };
+            layout(set = 1, binding = 1) buffer Counters { uint counters[]; };
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 color;
+
+            void main() {
+                color = globals.tint * texture(textures[1], v_uv) * lights[0];
+                counters[0] += 1u;
+            }
+        ", shaderc::ShaderKind::Fragment);
+
+        let reflection = reflect(&words).unwrap();
+        let layout = &reflection.layout;
+        assert_eq!(reflection.stage, ShaderStage::Fragment);
+        assert_eq!(layout.num_sets(), 2);
+        assert_eq!(layout.num_bindings_in_set(0), Some(3));
+        assert_eq!(layout.num_bindings_in_set(1), Some(2));
+        assert!(layout.descriptor(0, 1).is_none());
+
+        let globals = descriptor(layout, 0, 0);
+        assert!(matches!(globals.ty, DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: false, .. })));
+        assert!(globals.readonly);
+        assert!(globals.stages.fragment && !globals.stages.vertex);
+
+        let textures = descriptor(layout, 0, 2);
+        assert!(matches!(textures.ty, DescriptorDescTy::CombinedImageSampler(DescriptorImageDesc {
+            dimensions: DescriptorImageDescDimensions::TwoDimensional,
+            ..
+        })));
+        assert_eq!(textures.array_count, 3);
+
+        let lights = descriptor(layout, 1, 0);
+        assert!(matches!(lights.ty, DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: true, .. })));
+        assert!(lights.readonly);
+
+        let counters = descriptor(layout, 1, 1);
+        assert!(matches!(counters.ty, DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: true, .. })));
+        assert!(!counters.readonly);
+    }
+
+    #[test]
+    fn reflects_push_constant_size() {
+        let words = compile("
+            #version 450
+            layout(push_constant) uniform Push {
+                mat4 transform;
+                vec2 offset;
+                float scale;
+            } push;
+
+            void main() {
+                gl_Position = push.transform * vec4(push.offset * push.scale, 0.0, 1.0);
+            }
+        ", shaderc::ShaderKind::Vertex);
+
+        let layout = reflect(&words).unwrap().layout;
+        let range = layout.push_constants_range(0).expect("missing push constants");
+        assert_eq!(layout.num_push_constants_ranges(), 1);
+        assert_eq!(range.offset, 0);
+        assert_eq!(range.size, 64 + 8 + 4);
+        assert!(range.stages.vertex && !range.stages.fragment);
+    }
+
+    #[test]
+    fn spec_constant_array_lengths_use_the_default_value() {
+        let words = compile("
+            #version 450
+            layout(constant_id = 0) const int TEXTURE_COUNT = 4;
+            layout(constant_id = 1) const int WEIGHT_COUNT = 5;
+            layout(set = 0, binding = 0) uniform sampler2D textures[TEXTURE_COUNT];
+            layout(push_constant) uniform Push { float weights[WEIGHT_COUNT]; } push;
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 color;
+
+            void main() {
+                color = texture(textures[0], v_uv) * push.weights[0];
+            }
+        ", shaderc::ShaderKind::Fragment);
+
+        let layout = reflect(&words).unwrap().layout;
+        assert_eq!(descriptor(&layout, 0, 0).array_count, 4);
+        assert_eq!(layout.push_constants_range(0).unwrap().size, 5 * 4);
+    }
+
+    #[test]
+    fn spec_constant_expression_array_lengths_are_an_error() {
+        let words = compile("
+            #version 450
+            layout(constant_id = 0) const int TEXTURE_COUNT = 4;
+            layout(set = 0, binding = 0) uniform sampler2D textures[TEXTURE_COUNT * 2];
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 color;
+
+            void main() {
+                color = texture(textures[0], v_uv);
+            }
+        ", shaderc::ShaderKind::Fragment);
+
+        let err = reflect(&words).err().expect("expression lengths should not reflect");
+        assert!(err.to_string().contains("specialization constant expression"), "{}", err);
+    }
+}
@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} push;
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragUv;
+
+void main() {
+    gl_Position = vec4(2.0 * position / push.screen_size - 1.0, 0.0, 1.0);
+    fragColor = color;
+    fragUv = uv;
+}
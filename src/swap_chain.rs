@@ -0,0 +1,124 @@
+use vulkano::format::Format;
+use vulkano::swapchain::{ColorSpace, PresentMode};
+
+pub fn choose_swap_surface_format(available_formats: &[(Format, ColorSpace)]) -> Option<(Format, ColorSpace)> {
+    available_formats.iter()
+        .find(|(format, color_space)|
+                *format == Format::B8G8R8A8Unorm && *color_space == ColorSpace::SrgbNonLinear
+            )
+        .or_else(|| available_formats.first())
+        .copied()
+}
+
+pub fn choose_swap_present_mode(available_present_modes: &[PresentMode]) -> PresentMode {
+    if available_present_modes.contains(&PresentMode::Mailbox) {
+        PresentMode::Mailbox
+    } else if available_present_modes.contains(&PresentMode::Immediate) {
+        PresentMode::Immediate
+    } else {
+        PresentMode::Fifo
+    }
+}
+
+pub fn choose_swap_extent(
+    current_extent: Option<[u32; 2]>,
+    min_image_extent: [u32; 2],
+    max_image_extent: [u32; 2],
+    window_extent: [u32; 2],
+) -> [u32; 2] {
+    if let Some(current_extent) = current_extent {
+        return current_extent
+    }
+
+    [
+        min_image_extent[0].max(max_image_extent[0].min(window_extent[0])),
+        min_image_extent[1].max(max_image_extent[1].min(window_extent[1])),
+    ]
+}
+
+pub fn is_drawable_extent(extent: [u32; 2]) -> bool {
+    extent[0] > 0 && extent[1] > 0
+}
+
+pub fn choose_image_count(min_image_count: u32, max_image_count: Option<u32>) -> u32 {
+    let image_count = min_image_count + 1;
+    match max_image_count {
+        Some(max) if image_count > max => max,
+        _ => image_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_format_prefers_bgra_srgb() {
+        let formats = [
+            (Format::R8G8B8A8Unorm, ColorSpace::SrgbNonLinear),
+            (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear),
+        ];
+        assert_eq!(
+            choose_swap_surface_format(&formats),
+            Some((Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear))
+        );
+    }
+
+    #[test]
+    fn surface_format_falls_back_to_first() {
+        let formats = [
+            (Format::R8G8B8A8Unorm, ColorSpace::SrgbNonLinear),
+            (Format::B8G8R8A8Unorm, ColorSpace::ExtendedSrgbLinear),
+        ];
+        assert_eq!(
+            choose_swap_surface_format(&formats),
+            Some((Format::R8G8B8A8Unorm, ColorSpace::SrgbNonLinear))
+        );
+    }
+
+    #[test]
+    fn surface_format_empty_list() {
+        assert_eq!(choose_swap_surface_format(&[]), None);
+    }
+
+    #[test]
+    fn present_mode_preference_order() {
+        let all = [PresentMode::Fifo, PresentMode::Immediate, PresentMode::Mailbox];
+        assert_eq!(choose_swap_present_mode(&all), PresentMode::Mailbox);
+        assert_eq!(choose_swap_present_mode(&all[..2]), PresentMode::Immediate);
+        assert_eq!(choose_swap_present_mode(&all[..1]), PresentMode::Fifo);
+        assert_eq!(choose_swap_present_mode(&[]), PresentMode::Fifo);
+    }
+
+    #[test]
+    fn extent_uses_current_extent_when_set() {
+        let extent = choose_swap_extent(Some([1024, 768]), [1, 1], [4096, 4096], [800, 600]);
+        assert_eq!(extent, [1024, 768]);
+    }
+
+    #[test]
+    fn extent_clamps_window_size_without_current_extent() {
+        assert_eq!(choose_swap_extent(None, [1, 1], [4096, 4096], [800, 600]), [800, 600]);
+        assert_eq!(choose_swap_extent(None, [1, 1], [640, 480], [800, 600]), [640, 480]);
+        assert_eq!(choose_swap_extent(None, [1000, 700], [4096, 4096], [800, 600]), [1000, 700]);
+    }
+
+    #[test]
+    fn zero_extent_is_not_drawable() {
+        assert!(is_drawable_extent([800, 600]));
+        assert!(!is_drawable_extent([0, 0]));
+        assert!(!is_drawable_extent([800, 0]));
+        assert!(!is_drawable_extent([0, 600]));
+    }
+
+    #[test]
+    fn image_count_without_max() {
+        assert_eq!(choose_image_count(2, None), 3);
+    }
+
+    #[test]
+    fn image_count_clamped_to_max() {
+        assert_eq!(choose_image_count(2, Some(8)), 3);
+        assert_eq!(choose_image_count(3, Some(3)), 3);
+    }
+}
@@ -0,0 +1,349 @@
+use vulkano::buffer::CpuBufferPool;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::image::ImmutableImage;
+use vulkano::pipeline::{
+        cache::PipelineCache,
+        vertex::SingleBufferDefinition,
+    };
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::pipeline_desc::{BlendMode, CullMode, PipelineDesc};
+use crate::pipeline_registry::{PipelineId, PipelineRegistry, PipelineResult};
+use crate::uploader::Uploader;
+
+const ATLAS_WIDTH: usize = 512;
+const GLYPH_PADDING: usize = 1;
+const FIRST_CHAR: u8 = 0x20;
+const LAST_CHAR: u8 = 0x7e;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shader_text.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shader_text.frag"
+    }
+}
+
+#[derive(Debug)]
+pub enum TextError {
+    Font(&'static str),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextError::Font(err) => write!(f, "failed to parse font: {}", err),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TextVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+vulkano::impl_vertex!(TextVertex, position, uv, color);
+
+// Placement of a glyph relative to the pen position on the baseline, in pixels with Y
+// pointing down, and its rectangle in the atlas.
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    advance: f32,
+}
+
+// Printable ASCII rasterized once at a fixed pixel size and packed row by row into a
+// single channel coverage texture. The atlas is widened when a glyph does not fit in
+// ATLAS_WIDTH, which only happens at very large pixel sizes.
+struct GlyphAtlas {
+    glyphs: HashMap<char, Glyph>,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    ascent: f32,
+    line_height: f32,
+}
+
+impl GlyphAtlas {
+    fn new(font: &fontdue::Font, pixel_size: f32) -> Self {
+        let rasterized: Vec<_> = (FIRST_CHAR..=LAST_CHAR)
+            .map(char::from)
+            .map(|character| (character, font.rasterize(character, pixel_size)))
+            .collect();
+
+        let widest = rasterized.iter().map(|(_, (metrics, _))| metrics.width).max().unwrap_or(0);
+        let width = ATLAS_WIDTH.max((widest + 2 * GLYPH_PADDING).next_power_of_two());
+
+        // First pass: place every glyph to find out how tall the atlas has to be.
+        let mut placements = Vec::with_capacity(rasterized.len());
+        let (mut x, mut y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+        for (_, (metrics, _)) in &rasterized {
+            if x + metrics.width + GLYPH_PADDING > width {
+                x = GLYPH_PADDING;
+                y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            placements.push((x, y));
+            x += metrics.width + GLYPH_PADDING;
+            row_height = row_height.max(metrics.height);
+        }
+        let height = (y + row_height + GLYPH_PADDING).next_power_of_two();
+
+        let mut pixels = vec![0; width * height];
+        let mut glyphs = HashMap::new();
+        for ((character, (metrics, bitmap)), (x, y)) in rasterized.iter().zip(placements) {
+            for row in 0..metrics.height {
+                let source = &bitmap[row * metrics.width..(row + 1) * metrics.width];
+                let start = (y + row) * width + x;
+                pixels[start..start + metrics.width].copy_from_slice(source);
+            }
+
+            glyphs.insert(*character, Glyph {
+                offset: [metrics.xmin as f32, -(metrics.ymin as f32 + metrics.height as f32)],
+                size: [metrics.width as f32, metrics.height as f32],
+                uv_min: [x as f32 / width as f32, y as f32 / height as f32],
+                uv_max: [
+                    (x + metrics.width) as f32 / width as f32,
+                    (y + metrics.height) as f32 / height as f32,
+                ],
+                advance: metrics.advance_width,
+            });
+        }
+
+        let (ascent, line_height) = match font.horizontal_line_metrics(pixel_size) {
+            Some(line_metrics) => (line_metrics.ascent, line_metrics.new_line_size),
+            None => (pixel_size, pixel_size),
+        };
+
+        Self { glyphs, width, height, pixels, ascent, line_height }
+    }
+}
+
+// Lightweight screen-space text for labels and counters, independent of the GUI overlay.
+// Text is queued every frame with `text` and drawn as textured quads in a single draw.
+pub struct TextRenderer {
+    atlas: GlyphAtlas,
+    atlas_image: Arc<ImmutableImage<Format>>,
+    sampler: Arc<Sampler>,
+
+    vertices: Vec<TextVertex>,
+    pool: CpuBufferPool<TextVertex>,
+
+    pipeline: Option<PipelineId<SingleBufferDefinition<TextVertex>>>,
+    atlas_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+}
+
+impl TextRenderer {
+    pub fn new(device: &Arc<Device>, uploader: &mut Uploader, font: &[u8], pixel_size: f32) -> Result<Self, TextError> {
+        let font = fontdue::Font::from_bytes(font, fontdue::FontSettings::default())
+            .map_err(TextError::Font)?;
+
+        let atlas = GlyphAtlas::new(&font, pixel_size);
+        let atlas_image = uploader.upload_image(
+                                atlas.pixels.iter().cloned(),
+                                [atlas.width as u32, atlas.height as u32],
+                                Format::R8Unorm
+                            );
+
+        // Clamped so that glyphs on the atlas border don't sample the opposite edge.
+        let sampler = Sampler::new(
+                            device.clone(),
+                            Filter::Linear,
+                            Filter::Linear,
+                            MipmapMode::Nearest,
+                            SamplerAddressMode::ClampToEdge,
+                            SamplerAddressMode::ClampToEdge,
+                            SamplerAddressMode::ClampToEdge,
+                            0.0, 1.0, 0.0, 0.0
+                        ).expect("failed to create text sampler!");
+
+        Ok(Self {
+            atlas,
+            atlas_image,
+            sampler,
+
+            vertices: Vec::new(),
+            pool: CpuBufferPool::vertex_buffer(device.clone()),
+
+            pipeline: None,
+            atlas_set: None,
+        })
+    }
+
+    pub fn register_pipeline(
+        &mut self,
+        pipelines: &mut PipelineRegistry,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        subpass: u32,
+    ) {
+        let desc = PipelineDesc {
+            cull_mode: CullMode::None,
+            blend_mode: BlendMode::Alpha,
+            .. PipelineDesc::default()
+        };
+        let id = pipelines.get_or_create("text", &desc, render_pass, subpass, Self::create_graphics_pipeline);
+
+        let layout = pipelines.get(id).descriptor_set_layout(0)
+            .expect("text fragment shader has no descriptor set")
+            .clone();
+        self.atlas_set = Some(Arc::new(PersistentDescriptorSet::start(layout)
+            .add_sampled_image(self.atlas_image.clone(), self.sampler.clone()).expect("failed to bind glyph atlas")
+            .build().expect("failed to build text descriptor set")
+        ));
+        self.pipeline = Some(id);
+    }
+
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        desc: &PipelineDesc,
+        swap_chain_extent: [u32; 2],
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        pipeline_cache: &Arc<PipelineCache>,
+    ) -> PipelineResult<SingleBufferDefinition<TextVertex>> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+                .expect("failed to create text vertex shader module!");
+
+        let frag_shader_module = fragment_shader::Shader::load(device.clone())
+                .expect("failed to create text fragment shader module!");
+
+        desc.build(
+                device,
+                SingleBufferDefinition::<TextVertex>::new(),
+                vert_shader_module.main_entry_point(),
+                frag_shader_module.main_entry_point(),
+                swap_chain_extent,
+                subpass,
+                pipeline_cache
+            )
+    }
+
+    // Queues `text` with its top-left corner at `position`, in pixels from the top-left
+    // corner of the window. Characters outside printable ASCII are skipped.
+    pub fn text(&mut self, position: [f32; 2], text: &str, color: [f32; 4]) {
+        let mut pen = [position[0], position[1] + self.atlas.ascent];
+
+        for character in text.chars() {
+            if character == '\n' {
+                pen = [position[0], pen[1] + self.atlas.line_height];
+                continue;
+            }
+
+            let glyph = match self.atlas.glyphs.get(&character) {
+                Some(glyph) => *glyph,
+                None => continue,
+            };
+
+            if glyph.size[0] > 0.0 && glyph.size[1] > 0.0 {
+                let min = [(pen[0] + glyph.offset[0]).round(), (pen[1] + glyph.offset[1]).round()];
+                let max = [min[0] + glyph.size[0], min[1] + glyph.size[1]];
+                let vertex = |x: usize, y: usize| TextVertex {
+                    position: [[min[0], max[0]][x], [min[1], max[1]][y]],
+                    uv: [[glyph.uv_min[0], glyph.uv_max[0]][x], [glyph.uv_min[1], glyph.uv_max[1]][y]],
+                    color,
+                };
+
+                self.vertices.extend_from_slice(&[
+                    vertex(0, 0), vertex(1, 0), vertex(1, 1),
+                    vertex(0, 0), vertex(1, 1), vertex(0, 1),
+                ]);
+            }
+
+            pen[0] += glyph.advance;
+        }
+    }
+
+    pub fn record_draw(&mut self, builder: &mut AutoCommandBufferBuilder, pipelines: &PipelineRegistry) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let pipeline = pipelines.get(self.pipeline.expect("text pipeline not registered"));
+        let atlas_set = self.atlas_set.clone()
+            .expect("text pipeline not registered");
+        let extent = pipelines.extent();
+        let vertices = self.pool.chunk(self.vertices.drain(..))
+            .expect("failed to allocate text vertex buffer");
+        let push_constants = vertex_shader::ty::PushConstants {
+            screen_size: [extent[0] as f32, extent[1] as f32],
+        };
+
+        builder.draw(pipeline, &DynamicState::none(), Arc::new(vertices), atlas_set, push_constants)
+            .expect("failed to draw text");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+    fn font() -> fontdue::Font {
+        fontdue::Font::from_bytes(FONT, fontdue::FontSettings::default()).unwrap()
+    }
+
+    #[test]
+    fn atlas_holds_printable_ascii() {
+        let atlas = GlyphAtlas::new(&font(), 18.0);
+
+        assert_eq!(atlas.glyphs.len(), usize::from(LAST_CHAR - FIRST_CHAR + 1));
+        assert_eq!(atlas.width, ATLAS_WIDTH);
+        assert!(atlas.height.is_power_of_two());
+        assert_eq!(atlas.pixels.len(), atlas.width * atlas.height);
+    }
+
+    #[test]
+    fn glyphs_stay_inside_the_atlas() {
+        let atlas = GlyphAtlas::new(&font(), 18.0);
+
+        for glyph in atlas.glyphs.values() {
+            assert!(glyph.uv_min[0] >= 0.0 && glyph.uv_min[1] >= 0.0);
+            assert!(glyph.uv_max[0] <= 1.0 && glyph.uv_max[1] <= 1.0);
+            assert!(glyph.uv_min[0] <= glyph.uv_max[0] && glyph.uv_min[1] <= glyph.uv_max[1]);
+        }
+    }
+
+    #[test]
+    fn atlas_grows_for_glyphs_wider_than_its_width() {
+        let atlas = GlyphAtlas::new(&font(), 600.0);
+        let widest = atlas.glyphs.values().map(|glyph| glyph.size[0] as usize).max().unwrap();
+
+        assert!(widest + 2 * GLYPH_PADDING > ATLAS_WIDTH);
+        assert!(atlas.width >= widest + 2 * GLYPH_PADDING);
+        assert!(atlas.width.is_power_of_two());
+        for glyph in atlas.glyphs.values() {
+            assert!(glyph.uv_max[0] <= 1.0 && glyph.uv_max[1] <= 1.0);
+        }
+    }
+
+    #[test]
+    fn text_is_laid_out_on_the_baseline() {
+        let atlas = GlyphAtlas::new(&font(), 18.0);
+
+        assert!(atlas.ascent > 0.0 && atlas.ascent < atlas.line_height);
+        let space = atlas.glyphs[&' '];
+        assert_eq!(space.size, [0.0, 0.0]);
+        assert!(space.advance > 0.0);
+    }
+}
@@ -0,0 +1,143 @@
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, ImageLayout, ImageUsage, ImmutableImage, MipmapsCount};
+use vulkano::instance::QueueFamily;
+use vulkano::sync::GpuFuture;
+
+use std::sync::Arc;
+
+// Batches uploads through staging buffers and submits them on the transfer queue.
+//
+// Vulkano 0.20 has no explicit queue family ownership transfer barriers, so every
+// destination resource is created with concurrent sharing between the transfer
+// family and the families that will read it.
+pub struct Uploader {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    families: Vec<u32>,
+
+    builder: Option<AutoCommandBufferBuilder>,
+    pending_future: Option<Box<dyn GpuFuture>>,
+}
+
+impl Uploader {
+    pub fn new(device: &Arc<Device>, transfer_queue: &Arc<Queue>, consumer_queues: &[&Arc<Queue>]) -> Self {
+        let mut families = vec![transfer_queue.family().id()];
+        for queue in consumer_queues {
+            let id = queue.family().id();
+            if !families.contains(&id) {
+                families.push(id);
+            }
+        }
+
+        Self {
+            device: device.clone(),
+            queue: transfer_queue.clone(),
+            families,
+
+            builder: None,
+            pending_future: None,
+        }
+    }
+
+    fn queue_families(&self) -> Vec<QueueFamily<'_>> {
+        let physical_device = self.device.physical_device();
+        self.families.iter()
+            .map(|id| physical_device.queue_family_by_id(*id).unwrap())
+            .collect()
+    }
+
+    fn builder(&mut self) -> &mut AutoCommandBufferBuilder {
+        let device = &self.device;
+        let queue_family = self.queue.family();
+        self.builder.get_or_insert_with(|| {
+            AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue_family)
+                .expect("failed to initialise upload command buffer builder")
+        })
+    }
+
+    pub fn upload_buffer<T, I>(&mut self, data: I, usage: BufferUsage) -> Arc<DeviceLocalBuffer<[T]>>
+    where
+        T: Send + Sync + 'static,
+        I: ExactSizeIterator<Item = T>,
+    {
+        let len = data.len();
+        let staging = CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::transfer_source(),
+                        false,
+                        data
+                    ).expect("failed to create staging buffer!");
+
+        let buffer = DeviceLocalBuffer::array(
+                        self.device.clone(),
+                        len,
+                        BufferUsage { transfer_destination: true, .. usage },
+                        self.queue_families()
+                    ).expect("failed to create device local buffer!");
+
+        self.builder()
+            .copy_buffer(staging, buffer.clone())
+            .expect("failed to record buffer upload");
+
+        buffer
+    }
+
+    pub fn upload_image<I>(&mut self, data: I, dimensions: [u32; 2], format: Format) -> Arc<ImmutableImage<Format>>
+    where
+        I: ExactSizeIterator<Item = u8>,
+    {
+        let staging = CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::transfer_source(),
+                        false,
+                        data
+                    ).expect("failed to create staging buffer!");
+
+        let usage = ImageUsage {
+            transfer_destination: true,
+            sampled: true,
+            .. ImageUsage::none()
+        };
+
+        let (image, initialization) = ImmutableImage::uninitialized(
+                        self.device.clone(),
+                        Dimensions::Dim2d { width: dimensions[0], height: dimensions[1] },
+                        format,
+                        MipmapsCount::One,
+                        usage,
+                        ImageLayout::ShaderReadOnlyOptimal,
+                        self.queue_families()
+                    ).expect("failed to create image!");
+
+        self.builder()
+            .copy_buffer_to_image(staging, initialization)
+            .expect("failed to record image upload");
+
+        image
+    }
+
+    pub fn flush(&mut self) {
+        let builder = match self.builder.take() {
+            Some(builder) => builder,
+            None => return,
+        };
+
+        let command_buffer = builder.build().expect("failed to build upload command buffer");
+        let future = command_buffer.execute(self.queue.clone())
+            .expect("failed to submit uploads")
+            .then_signal_semaphore_and_flush()
+            .expect("failed to flush uploads");
+
+        self.pending_future = Some(match self.pending_future.take() {
+            Some(pending) => Box::new(pending.join(future)) as Box<dyn GpuFuture>,
+            None => Box::new(future) as Box<dyn GpuFuture>,
+        });
+    }
+
+    pub fn take_future(&mut self) -> Option<Box<dyn GpuFuture>> {
+        self.pending_future.take()
+    }
+}
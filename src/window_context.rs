@@ -0,0 +1,91 @@
+use vulkano::command_buffer::AutoCommandBuffer;
+use vulkano::device::Queue;
+use vulkano::framebuffer::{FramebufferAbstract, RenderPassAbstract};
+use vulkano::image::swapchain::SwapchainImage;
+use vulkano::swapchain::{Surface, Swapchain};
+use vulkano::sync::GpuFuture;
+use winit::window::{Window, WindowId};
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::gpu_queries::{GpuTimer, SceneQueries};
+use crate::pipeline_registry::{PipelineId, PipelineRegistry};
+use crate::swap_chain;
+use crate::window_mode::WindowMode;
+
+#[derive(Debug)]
+pub enum WindowError {
+    // The device was picked for the primary window and may not suit the others.
+    UnsuitableDevice,
+    NoPresentQueue,
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowError::UnsuitableDevice => write!(f, "the selected device can't present to the window"),
+            WindowError::NoPresentQueue => write!(f, "no queue of the selected device can present to the window"),
+        }
+    }
+}
+
+// Everything tied to one window: its surface and swap chain, the render pass and
+// pipelines built for the swap chain's format and extent, and the last frame submitted
+// to it. The device, the graphics queue and the rest of the renderer are shared by all
+// windows, while each presents on a queue that supports its surface.
+pub struct WindowContext {
+    pub surface: Arc<Surface<Window>>,
+    pub window_mode: WindowMode,
+    pub present_queue: Arc<Queue>,
+
+    pub swap_chain: Arc<Swapchain<Window>>,
+    pub swap_chain_images: Vec<Arc<SwapchainImage<Window>>>,
+
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub pipelines: PipelineRegistry,
+    pub main_pipeline: PipelineId,
+
+    pub framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    // Empty when the frames are recorded every frame instead.
+    pub command_buffers: Vec<Arc<AutoCommandBuffer>>,
+
+    pub previous_frame_end: Option<Box<dyn GpuFuture>>,
+    pub gpu_timer: Option<GpuTimer>,
+    pub scene_queries: Option<SceneQueries>,
+    pub recreate_swap_chain: bool,
+    pub minimized: bool,
+}
+
+impl WindowContext {
+    pub fn id(&self) -> WindowId {
+        self.window().id()
+    }
+
+    pub fn window(&self) -> &Window {
+        self.surface.window()
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        window_extent(self.window())
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        !swap_chain::is_drawable_extent(self.extent())
+    }
+
+    // The window resizes to the monitor's resolution and reports it with a `Resized`
+    // event. The swap chain is recreated right away as it may also have to change its
+    // fullscreen exclusive setting.
+    pub fn set_window_mode(&mut self, window_mode: WindowMode) {
+        let window = self.surface.window();
+        window.set_fullscreen(window_mode.fullscreen(window.current_monitor()));
+        self.window_mode = window_mode;
+        self.recreate_swap_chain = true;
+    }
+}
+
+pub fn window_extent(window: &Window) -> [u32; 2] {
+    let size = window.inner_size();
+    [size.width, size.height]
+}
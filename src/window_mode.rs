@@ -0,0 +1,51 @@
+use vulkano::device::DeviceExtensions;
+use vulkano::swapchain::FullscreenExclusive;
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::Fullscreen;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    // A window without decorations covering the whole monitor at its current resolution.
+    Borderless,
+    // Switches the monitor to its largest video mode. Falls back to borderless when the
+    // monitor does not report any video mode.
+    Exclusive,
+}
+
+impl WindowMode {
+    pub fn is_fullscreen(self) -> bool {
+        self != WindowMode::Windowed
+    }
+
+    // `monitor` is the monitor the window is on, or the primary one before the window
+    // exists. `None` lets the platform pick one.
+    pub fn fullscreen(self, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+        match self {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Exclusive => match monitor.as_ref().and_then(best_video_mode) {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => Some(Fullscreen::Borderless(monitor)),
+            },
+        }
+    }
+
+    // Lets the driver give exclusive fullscreen windows direct control of the display.
+    // Setting anything but `Default` needs the `ext_full_screen_exclusive` extension.
+    pub fn fullscreen_exclusive(self, enabled_extensions: &DeviceExtensions) -> FullscreenExclusive {
+        if self == WindowMode::Exclusive && enabled_extensions.ext_full_screen_exclusive {
+            FullscreenExclusive::Allowed
+        } else {
+            FullscreenExclusive::Default
+        }
+    }
+}
+
+fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    monitor.video_modes()
+        .max_by_key(|video_mode| {
+            let size = video_mode.size();
+            (size.width * size.height, video_mode.bit_depth(), video_mode.refresh_rate())
+        })
+}